@@ -0,0 +1,25 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[derive(Clone)]
+#[serde(default = "ConfLibrary::init")]
+pub struct ConfLibrary {
+    /// Genre tags are split into several genres on these, e.g. `Rock; Blues`
+    pub genre_separators: Vec<String>,
+}
+
+impl ConfLibrary {
+    pub fn init() -> ConfLibrary {
+        ConfLibrary{
+            genre_separators: vec![";".to_string(), "/".to_string()],
+        }
+    }
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
@@ -0,0 +1,76 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+//-////////////////////////////////////////////////////////////////////////////
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfCrossfadeCurve {
+    Linear,
+    EqualPower,
+}
+
+impl ConfCrossfadeCurve {
+    /// (outgoing gain, incoming gain) at `progress` 0.0..=1.0 through the fade
+    pub fn gains(&self, progress: f32) -> (f32, f32) {
+        match self {
+            ConfCrossfadeCurve::Linear     => (1.0 - progress, progress),
+            ConfCrossfadeCurve::EqualPower => {
+                let angle = progress * std::f32::consts::FRAC_PI_2;
+                (angle.cos(), angle.sin())
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfReplayGain {
+    Off,
+    Track,
+    Album,
+    /// album gain while the playlist plays an album in order, track gain otherwise
+    Auto,
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy)]
+#[serde(default = "ConfPlayback::init")]
+pub struct ConfPlayback {
+    /// Overlap the end of a track with the start of the next. Never applied within an album.
+    pub crossfade: bool,
+    pub crossfade_sec: f32,
+    pub crossfade_curve: ConfCrossfadeCurve,
+    pub replay_gain: ConfReplayGain,
+    /// Gain in dB for tracks without ReplayGain tags
+    pub replay_gain_preamp_db: f32,
+    /// Start paused at the saved position when the previous session is restored
+    pub resume_paused: bool,
+    /// Undoing a queue edit also goes back to the playback position from before the edit
+    pub undo_restores_position: bool,
+}
+
+impl ConfPlayback {
+    pub fn init() -> ConfPlayback {
+        ConfPlayback{
+            crossfade: false,
+            crossfade_sec: 5.0,
+            crossfade_curve: ConfCrossfadeCurve::EqualPower,
+            replay_gain: ConfReplayGain::Auto,
+            replay_gain_preamp_db: 0.0,
+            resume_paused: false,
+            undo_restores_position: true,
+        }
+    }
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
@@ -24,6 +24,8 @@ pub struct ConfTheme {
     pub icon_color_done   : Color,
     pub icon_color_playing: Color,
     pub icon_color_queued : Color,
+    #[serde(default = "ConfTheme::init_icon_color_broken")]
+    pub icon_color_broken : Color,
 }
 
 impl ConfTheme {
@@ -44,8 +46,13 @@ impl ConfTheme {
             icon_color_done   : Color::Red,
             icon_color_playing: Color::Yellow,
             icon_color_queued : Color::Green,
+            icon_color_broken : ConfTheme::init_icon_color_broken(),
         }
     }
+
+    fn init_icon_color_broken() -> Color {
+        Color::Magenta
+    }
 }
 //-////////////////////////////////////////////////////////////////////////////
 //
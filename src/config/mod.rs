@@ -1,11 +1,15 @@
 pub mod conf_color;
+pub mod conf_library;
 pub mod conf_logs;
+pub mod conf_playback;
 pub mod conf_theme;
 
 //-//////////////////////////////////////////////////////////////////
 
 use crate::config::conf_color::ConfColor;
+use crate::config::conf_library::ConfLibrary;
 use crate::config::conf_logs::ConfLog;
+use crate::config::conf_playback::ConfPlayback;
 use crate::config::conf_theme::ConfTheme;
 use color_eyre::eyre::Context;
 use color_eyre::eyre::ContextCompat;
@@ -27,6 +31,10 @@ use std::path::PathBuf;
 pub struct Config {
     pub framerate: u16,
     pub media_dirs: Vec<PathBuf>,
+    #[serde(default = "ConfPlayback::init")]
+    pub playback: ConfPlayback,
+    #[serde(default = "ConfLibrary::init")]
+    pub library: ConfLibrary,
     pub logging: ConfLog,
     pub theme: ConfTheme,
     pub color: ConfColor,
@@ -42,6 +50,8 @@ impl Config {
         let config_file = Config{
             framerate: 60,
             media_dirs,
+            playback: ConfPlayback::init(),
+            library: ConfLibrary::init(),
             logging: ConfLog::init(),
             theme: ConfTheme::init(),
             color: ConfColor::init(),
@@ -75,7 +85,7 @@ impl Config {
 //-//////////////////////////////////////////////////////////////////
 //
 //-//////////////////////////////////////////////////////////////////
-fn expand_home_dir(base_dirs: &Option<BaseDirs>, path: PathBuf) -> Result<PathBuf> {
+pub fn expand_home_dir(base_dirs: &Option<BaseDirs>, path: PathBuf) -> Result<PathBuf> {
     match (path.starts_with("~"), base_dirs) {
         (false, _) => Ok(path),
         (true, None) => Err(eyre!("Could not find home dir to replace ~ in path {}", path.to_string_lossy())),
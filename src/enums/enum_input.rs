@@ -20,6 +20,12 @@ pub enum InputLocal {
 
     Select,
     SelectAlt,
+    SelectNext,
+
+    MoveUp,
+    MoveDown,
+    Remove,
+    ClearPlayed,
 }
 
 #[derive(Clone, Copy)]
@@ -32,6 +38,28 @@ pub enum InputGlobal {
     Previous,
     SkipForward{sec: u8},
     SkipBackward{sec: u8},
+    VolumeUp,
+    VolumeDown,
+    Mute,
+    Repeat,
+    Shuffle,
+    Radio,
+    Rescan,
+    ViewLibrary,
+    ViewPlaylist,
+    Undo,
+    Redo,
+}
+
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+/// Inputs while the command line is open
+pub enum InputCommand {
+    Open,
+    Char(char),
+    Backspace,
+    Submit,
+    Cancel,
 }
 
 //-//////////////////////////////////////////////////////////////////
@@ -53,6 +81,7 @@ pub enum InputLocalEffect {
 pub enum InputGlobalEffect {
     ReplaceTracksAndPlay{tracks: Vec<TrackFile>, index: usize},
     AppendTracks(Vec<TrackFile>),
+    InsertTracksNext(Vec<TrackFile>),
 }
 
 pub enum InputEffect {
@@ -0,0 +1,115 @@
+use color_eyre::eyre::Context;
+use color_eyre::eyre::ContextCompat;
+use color_eyre::Result;
+use directories::ProjectDirs;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::create_dir_all;
+use std::fs::read;
+use std::fs::read_to_string;
+use std::fs::rename;
+use std::fs::write;
+use std::path::Path;
+use std::path::PathBuf;
+
+//-////////////////////////////////////////////////////////////////////////////
+fn data_file_path(name: &str) -> Result<PathBuf> {
+    let path = ProjectDirs::from("", "", "music_player")
+        .context("Getting project paths")?
+        .data_dir()
+        .join(name);
+    Ok(path)
+}
+
+fn cache_file_path(name: &str) -> Result<PathBuf> {
+    let path = ProjectDirs::from("", "", "music_player")
+        .context("Getting project paths")?
+        .cache_dir()
+        .join(name);
+    Ok(path)
+}
+
+/// Reads a toml file from the project data dir, None if it has not been written yet
+pub fn read_data_file<T: DeserializeOwned>(name: &str) -> Result<Option<T>> {
+    read_toml_file(&data_file_path(name)?)
+}
+
+/// Writes a toml file to the project data dir
+pub fn write_data_file<T: Serialize>(name: &str, data: &T) -> Result<()> {
+    write_toml_file(&data_file_path(name)?, name, data)
+}
+
+/// Reads a binary file from the project cache dir, None if it has not been written yet.
+/// Cache files can hold the whole library, so they use postcard rather than toml.
+pub fn read_cache_file<T: DeserializeOwned>(name: &str) -> Result<Option<T>> {
+    let path = cache_file_path(name)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let raw = read(&path)
+        .context(format!("Reading cache file at {}", path.to_string_lossy()))?;
+    let parsed = postcard::from_bytes(&raw)
+        .context(format!("Parsing cache file at {}", path.to_string_lossy()))?;
+    Ok(Some(parsed))
+}
+
+/// Writes a binary file to the project cache dir
+pub fn write_cache_file<T: Serialize>(name: &str, data: &T) -> Result<()> {
+    let path = cache_file_path(name)?;
+    if let Some(dir) = path.parent().filter(|dir| !dir.exists()) {
+        create_dir_all(dir)
+            .context(format!("Creating cache dir: {}", dir.to_string_lossy()))?;
+    }
+    let raw = postcard::to_stdvec(data).context(format!("Serializing cache file {}", name))?;
+    replace_file(&path, raw)
+        .context(format!("Writing cache file at {}", path.to_string_lossy()))?;
+    Ok(())
+}
+
+fn read_toml_file<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let raw = read_to_string(path)
+        .context(format!("Reading data file at {}", path.to_string_lossy()))?;
+    let parsed = toml::from_str(&raw)
+        .context(format!("Parsing data file at {}", path.to_string_lossy()))?;
+    Ok(Some(parsed))
+}
+
+fn write_toml_file<T: Serialize>(path: &Path, name: &str, data: &T) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.exists()) {
+        create_dir_all(dir)
+            .context(format!("Creating data dir: {}", dir.to_string_lossy()))?;
+    }
+    let raw = toml::to_string(data).context(format!("Serializing data file {}", name))?;
+    replace_file(path, raw)
+        .context(format!("Writing data file at {}", path.to_string_lossy()))?;
+    Ok(())
+}
+
+/// Writes next to `path` first and renames over it, so a crash mid write leaves the old file intact
+fn replace_file(path: &Path, raw: impl AsRef<[u8]>) -> Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    write(&temp, raw)?;
+    rename(&temp, path)?;
+    Ok(())
+}
+//-////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_replace_file() {
+        let path = std::env::temp_dir().join(format!("music_player_replace_{}.toml", std::process::id()));
+        replace_file(&path, "old").unwrap();
+        replace_file(&path, "new").unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "new");
+        assert!(!path.with_extension("toml.tmp").exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,434 @@
+use color_eyre::eyre::bail;
+use color_eyre::eyre::Context;
+use color_eyre::Result;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+/// One entry of a playlist file, the path is absolute after reading
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct PlaylistFileEntry {
+    pub path    : PathBuf,
+    pub title   : Option<String>,
+    pub artist  : Option<String>,
+    pub album   : Option<String>,
+    pub duration: Option<Duration>,
+}
+
+/// Reads a playlist file, the format is picked from the file extension
+pub fn read_playlist_file(path: &Path) -> Result<Vec<PlaylistFileEntry>> {
+    let raw = fs::read(path)
+        .context(format!("Reading playlist file at {}", path.to_string_lossy()))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    match extension(path).as_str() {
+        "m3u8" => Ok(parse_m3u(&String::from_utf8_lossy(&raw), dir)),
+        // m3u without the 8 is latin-1 by convention, but utf-8 is common enough to try first
+        "m3u"  => match String::from_utf8(raw) {
+            Ok(text) => Ok(parse_m3u(&text, dir)),
+            Err(err) => Ok(parse_m3u(&err.into_bytes().into_iter().map(char::from).collect::<String>(), dir)),
+        },
+        "xspf" => Ok(parse_xspf(&String::from_utf8_lossy(&raw), dir)),
+        "pls"  => Ok(parse_pls(&String::from_utf8_lossy(&raw), dir)),
+        other => bail!("Unsupported playlist format: {:?}", other),
+    }
+}
+
+/// Writes a playlist file, the format is picked from the file extension
+pub fn write_playlist_file(path: &Path, entries: &[PlaylistFileEntry]) -> Result<()> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let raw = match extension(path).as_str() {
+        "m3u" | "m3u8" => render_m3u(entries, dir),
+        "xspf"         => render_xspf(entries, dir),
+        "pls"          => render_pls(entries, dir),
+        other => bail!("Unsupported playlist format: {:?}", other),
+    };
+    fs::write(path, raw)
+        .context(format!("Writing playlist file at {}", path.to_string_lossy()))?;
+    Ok(())
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().unwrap_or_default().to_string_lossy().to_lowercase()
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+fn parse_m3u(raw: &str, dir: &Path) -> Vec<PlaylistFileEntry> {
+    let mut entries = vec![];
+    let mut info: Option<(Option<Duration>, &str)> = None;
+    let mut artist: Option<String> = None;
+
+    for line in raw.trim_start_matches('\u{feff}').lines().map(str::trim) {
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            info = Some(parse_extinf(extinf));
+            continue;
+        }
+        if let Some(extart) = line.strip_prefix("#EXTART:") {
+            artist = Some(extart.trim().to_string()).filter(|artist| !artist.is_empty());
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (duration, name) = info.take().unwrap_or_default();
+        let artist = artist.take();
+        entries.push(PlaylistFileEntry{
+            path : resolve_location(line, dir),
+            title: strip_artist(name, artist.as_deref()),
+            artist,
+            album: None,
+            duration,
+        });
+    }
+    entries
+}
+
+/// `#EXTINF:<seconds> [attributes],<name>`
+fn parse_extinf(extinf: &str) -> (Option<Duration>, &str) {
+    let (info, name) = extinf.split_once(',').unwrap_or((extinf, ""));
+    let duration = info.split_whitespace()
+        .next()
+        .and_then(|secs| secs.parse::<f64>().ok())
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64);
+    (duration, name)
+}
+
+fn render_m3u(entries: &[PlaylistFileEntry], dir: &Path) -> String {
+    let mut output = String::from("#EXTM3U\n");
+    for entry in entries {
+        let secs = entry.duration.map(|duration| duration.as_secs() as i64).unwrap_or(-1);
+        writeln!(output, "#EXTINF:{},{}", secs, display_name(entry)).unwrap();
+        if let Some(artist) = &entry.artist {
+            writeln!(output, "#EXTART:{}", artist).unwrap();
+        }
+        writeln!(output, "{}", relative_location(&entry.path, dir).to_string_lossy()).unwrap();
+    }
+    output
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+fn parse_xspf(raw: &str, dir: &Path) -> Vec<PlaylistFileEntry> {
+    xml_elements(raw, "track")
+        .filter_map(|track| {
+            let text = |name: &str| xml_elements(track, name).next()
+                .map(|text| xml_unescape(text.trim()))
+                .filter(|text| !text.is_empty());
+            Some(PlaylistFileEntry{
+                path    : resolve_location(&percent_decode(&text("location")?), dir),
+                title   : text("title"),
+                artist  : text("creator"),
+                album   : text("album"),
+                duration: text("duration")
+                    .and_then(|millis| millis.parse::<u64>().ok())
+                    .map(Duration::from_millis),
+            })
+        })
+        .collect()
+}
+
+fn render_xspf(entries: &[PlaylistFileEntry], dir: &Path) -> String {
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    output.push_str("<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n");
+    output.push_str("  <trackList>\n");
+    for entry in entries {
+        let location = relative_location(&entry.path, dir);
+        let location = match location.is_absolute() {
+            true  => format!("file://{}", percent_encode(&location.to_string_lossy())),
+            false => percent_encode(&location.to_string_lossy()),
+        };
+        output.push_str("    <track>\n");
+        writeln!(output, "      <location>{}</location>", xml_escape(&location)).unwrap();
+        let fields = [("title", &entry.title), ("creator", &entry.artist), ("album", &entry.album)];
+        for (name, value) in fields {
+            if let Some(value) = value {
+                writeln!(output, "      <{}>{}</{}>", name, xml_escape(value), name).unwrap();
+            }
+        }
+        if let Some(duration) = entry.duration {
+            writeln!(output, "      <duration>{}</duration>", duration.as_millis()).unwrap();
+        }
+        output.push_str("    </track>\n");
+    }
+    output.push_str("  </trackList>\n");
+    output.push_str("</playlist>\n");
+    output
+}
+
+/// Contents of every `<name>` element, enough for the flat structure of xspf
+fn xml_elements<'a>(raw: &'a str, name: &str) -> impl Iterator<Item = &'a str> {
+    let open  = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut rest = raw;
+    std::iter::from_fn(move || loop {
+        let start = rest.find(&open)?;
+        let after = &rest[start+open.len()..];
+        // skip elements that only share the prefix, like <tracklist> for <track>
+        if !after.starts_with(['>', ' ', '\t', '\r', '\n', '/']) {
+            rest = after;
+            continue;
+        }
+        let tag_end = after.find('>')?;
+        if after[..tag_end].ends_with('/') {
+            rest = &after[tag_end+1..];
+            return Some("");
+        }
+        let content = &after[tag_end+1..];
+        let end = content.find(&close)?;
+        rest = &content[end+close.len()..];
+        return Some(&content[..end]);
+    })
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn xml_unescape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest   = text;
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest.find(';').map(|end| &rest[1..end]);
+        let decoded = match entity {
+            Some("amp")  => Some('&'),
+            Some("lt")   => Some('<'),
+            Some("gt")   => Some('>'),
+            Some("quot") => Some('"'),
+            Some("apos") => Some('\''),
+            Some(code) if code.starts_with("#x") => u32::from_str_radix(&code[2..], 16).ok().and_then(char::from_u32),
+            Some(code) if code.starts_with('#')  => code[1..].parse::<u32>().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        match (decoded, entity) {
+            (Some(c), Some(entity)) => {
+                output.push(c);
+                rest = &rest[entity.len()+2..];
+            },
+            _ => {
+                output.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Decodes `%XX` escapes of a uri, invalid escapes are kept as they are
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i+1..i+3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {decoded.push(byte); i += 3},
+            (byte, _)          => {decoded.push(byte); i += 1},
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Escapes everything except unreserved characters and `/`
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => encoded.push(byte as char),
+            byte => write!(encoded, "%{:02X}", byte).unwrap(),
+        }
+    }
+    encoded
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+fn parse_pls(raw: &str, dir: &Path) -> Vec<PlaylistFileEntry> {
+    let mut files   = BTreeMap::<u32, &str>::new();
+    let mut titles  = BTreeMap::<u32, &str>::new();
+    let mut lengths = BTreeMap::<u32, &str>::new();
+
+    for line in raw.trim_start_matches('\u{feff}').lines().map(str::trim) {
+        let Some((key, value)) = line.split_once('=') else {continue};
+        let key = key.trim().to_lowercase();
+        let (map, number) = match () {
+            _ if key.starts_with("file")   => (&mut files  , &key[4..]),
+            _ if key.starts_with("title")  => (&mut titles , &key[5..]),
+            _ if key.starts_with("length") => (&mut lengths, &key[6..]),
+            _ => continue,
+        };
+        if let Ok(number) = number.parse::<u32>() {
+            map.insert(number, value.trim());
+        }
+    }
+
+    files.into_iter()
+        .map(|(number, file)| {
+            PlaylistFileEntry{
+                path    : resolve_location(file, dir),
+                title   : titles.get(&number).and_then(|title| strip_artist(title, None)),
+                artist  : None,
+                album   : None,
+                duration: lengths.get(&number)
+                    .and_then(|secs| secs.parse::<i64>().ok())
+                    .and_then(|secs| u64::try_from(secs).ok())
+                    .map(Duration::from_secs),
+            }
+        })
+        .collect()
+}
+
+fn render_pls(entries: &[PlaylistFileEntry], dir: &Path) -> String {
+    let mut output = String::from("[playlist]\n");
+    for (number, entry) in (1..).zip(entries) {
+        let secs = entry.duration.map(|duration| duration.as_secs() as i64).unwrap_or(-1);
+        writeln!(output, "File{}={}", number, relative_location(&entry.path, dir).to_string_lossy()).unwrap();
+        writeln!(output, "Title{}={}", number, entry.title.as_deref().unwrap_or_default()).unwrap();
+        writeln!(output, "Length{}={}", number, secs).unwrap();
+    }
+    writeln!(output, "NumberOfEntries={}", entries.len()).unwrap();
+    output.push_str("Version=2\n");
+    output
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+/// `artist - title` as other players show an m3u entry, the artist is also written on its own with `#EXTART`
+fn display_name(entry: &PlaylistFileEntry) -> String {
+    match (&entry.artist, &entry.title) {
+        (Some(artist), Some(title)) => format!("{} - {}", artist, title),
+        (None        , Some(title)) => title.clone(),
+        (_           , None       ) => String::new(),
+    }
+}
+
+/// Title from a display name, only an artist the file names on its own is taken off the front
+fn strip_artist(name: &str, artist: Option<&str>) -> Option<String> {
+    let name  = name.trim();
+    let title = artist
+        .and_then(|artist| name.strip_prefix(artist))
+        .and_then(|title| title.strip_prefix(" - "))
+        .unwrap_or(name);
+    Some(title.trim().to_string()).filter(|title| !title.is_empty())
+}
+
+/// Absolute path for a location in a playlist, relative locations are relative to the playlist file
+fn resolve_location(location: &str, dir: &Path) -> PathBuf {
+    // file uris may name the local host, `file://localhost/music` is `/music`
+    let location = match location.strip_prefix("file://") {
+        Some(path) => path.strip_prefix("localhost").filter(|path| path.starts_with('/')).unwrap_or(path),
+        None       => location,
+    };
+    normalize(&dir.join(location))
+}
+
+/// Locations below the playlist directory are written relative to it, so the files can be moved together
+fn relative_location(path: &Path, dir: &Path) -> PathBuf {
+    match path.strip_prefix(dir) {
+        Ok(relative) if !dir.as_os_str().is_empty() => relative.to_path_buf(),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Removes `.` and `..` without touching the filesystem, scanned paths never contain them
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir    => {},
+            Component::ParentDir => {normalized.pop();},
+            component            => normalized.push(component),
+        }
+    }
+    normalized
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_m3u_roundtrip() {
+        let raw = "\u{feff}#EXTM3U\n#EXTINF:215,Artist - Song\n#EXTART:Artist\n./Artist/../Artist/01 Song.flac\n\n# comment\n/other/02 Other.mp3\n";
+        let entries = parse_m3u(raw, Path::new("/music"));
+        assert_eq!(entries, vec![
+            PlaylistFileEntry{
+                path    : PathBuf::from("/music/Artist/01 Song.flac"),
+                title   : Some("Song".to_string()),
+                artist  : Some("Artist".to_string()),
+                album   : None,
+                duration: Some(Duration::from_secs(215)),
+            },
+            PlaylistFileEntry{
+                path    : PathBuf::from("/other/02 Other.mp3"),
+                title   : None,
+                artist  : None,
+                album   : None,
+                duration: None,
+            },
+        ]);
+
+        let rendered = render_m3u(&entries, Path::new("/music"));
+        assert_eq!(rendered, "#EXTM3U\n#EXTINF:215,Artist - Song\n#EXTART:Artist\nArtist/01 Song.flac\n#EXTINF:-1,\n/other/02 Other.mp3\n");
+        assert_eq!(parse_m3u(&rendered, Path::new("/music")), entries);
+
+        // a dash in the name is part of the title unless the artist is named on its own
+        let entries = parse_m3u("#EXTINF:-1,Song - Live\nfile://localhost/music/03.flac\n", Path::new("/music"));
+        assert_eq!(entries[0].title, Some("Song - Live".to_string()));
+        assert_eq!(entries[0].artist, None);
+        assert_eq!(entries[0].path, PathBuf::from("/music/03.flac"));
+    }
+
+    #[test]
+    fn test_xspf_and_pls_roundtrip() {
+        let entries = vec![
+            PlaylistFileEntry{
+                path    : PathBuf::from("/music/A & B/01 Song #1.flac"),
+                title   : Some("Song <1>".to_string()),
+                artist  : Some("A & B".to_string()),
+                album   : Some("Album".to_string()),
+                duration: Some(Duration::from_millis(215_000)),
+            },
+            PlaylistFileEntry{
+                path    : PathBuf::from("/other/02.mp3"),
+                title   : None,
+                artist  : None,
+                album   : None,
+                duration: None,
+            },
+        ];
+        let dir = Path::new("/music");
+
+        let xspf = render_xspf(&entries, dir);
+        assert!(xspf.contains("<location>A%20%26%20B/01%20Song%20%231.flac</location>"));
+        assert!(xspf.contains("<location>file:///other/02.mp3</location>"));
+        assert_eq!(parse_xspf(&xspf, dir), entries);
+
+        let pls = render_pls(&entries, dir);
+        let mut title_only = entries.clone();
+        title_only[0].artist = None;
+        title_only[0].album  = None;
+        assert_eq!(parse_pls(&pls, dir), title_only);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::functions::functions_shuffle::shuffle;
+use crate::types::types_library_entry::TrackFile;
+use std::collections::BTreeSet;
+
+//-////////////////////////////////////////////////////////////////////////////
+/// years apart that still count as a neighbouring year
+const YEAR_RANGE: u16 = 2;
+
+/// Up to `count` library tracks related to `recent`, tracks in `avoid` are never picked.
+/// Same artist ranks above a shared genre or a neighbouring year, the rest of the library fills up when too little is related.
+pub fn pick_radio_tracks(library: &[TrackFile], recent: &[TrackFile], avoid: &BTreeSet<u64>, count: usize) -> Vec<TrackFile> {
+    let seed_genres = recent.iter()
+        .flat_map(|seed| seed.genre_ids.into_iter().flatten())
+        .collect::<BTreeSet<u64>>();
+    let score = |track: &TrackFile| {
+        let same_artist = recent.iter().any(|seed| seed.id_artist == track.id_artist);
+        let near_year   = recent.iter().any(|seed| match (seed.year, track.year) {
+            (Some(seed), Some(year)) => seed.abs_diff(year) <= YEAR_RANGE,
+            (_, _) => false,
+        });
+        let same_genre  = track.genre_ids.iter().flatten().any(|genre_id| seed_genres.contains(genre_id));
+        2 * same_artist as u8 + same_genre as u8 + near_year as u8
+    };
+
+    let mut candidates = library.iter()
+        .filter(|track| !avoid.contains(&track.id_track))
+        .map(|track| (score(track), *track))
+        .collect::<Vec<(u8, TrackFile)>>();
+    shuffle(&mut candidates);
+    candidates.sort_by(|(a, _), (b, _)| b.cmp(a));
+    candidates.into_iter()
+        .take(count)
+        .map(|(_, track)| track)
+        .collect()
+}
+//-////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn track(id: u64, artist: u64, year: u16) -> TrackFile {
+        TrackFile{id_artist: artist, year: Some(year), ..TrackFile::test(id)}
+    }
+
+    #[test]
+    fn test_radio_prefers_related_tracks() {
+        let library = vec![
+            track(1, 1, 1990),
+            track(2, 1, 2010),
+            track(3, 2, 1991),
+            track(4, 3, 2020),
+            track(5, 3, 2021),
+        ];
+        let ids = |tracks: Vec<TrackFile>| tracks.iter().map(|track| track.id_track).collect::<Vec<u64>>();
+        let avoid = BTreeSet::from([1]);
+
+        assert_eq!(ids(pick_radio_tracks(&library, &[library[0]], &avoid, 2)), vec![2, 3]);
+
+        let mut rest = ids(pick_radio_tracks(&library, &[library[0]], &avoid, 10));
+        rest[2..].sort();
+        assert_eq!(rest, vec![2, 3, 4, 5]);
+    }
+}
@@ -0,0 +1,47 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+//-////////////////////////////////////////////////////////////////////////////
+/// Fisher-Yates shuffle, randomness is good enough for playlists but not much else
+pub fn shuffle<T>(items: &mut [T]) {
+    let mut rng = Rng::new();
+    for i in (1..items.len()).rev() {
+        items.swap(i, rng.below(i + 1));
+    }
+}
+
+/// Random index in `0..len`, `len` must be above 0
+pub fn random_index(len: usize) -> usize {
+    Rng::new().below(len)
+}
+
+/// xorshift64 seeded from the randomly keyed std hasher
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Rng {
+        Rng(RandomState::new().hash_one(0u8) | 1)
+    }
+
+    fn below(&mut self, len: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % len as u64) as usize
+    }
+}
+//-////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_shuffle_keeps_items() {
+        let mut items = (0..100).collect::<Vec<usize>>();
+        shuffle(&mut items);
+        items.sort();
+        assert_eq!(items, (0..100).collect::<Vec<usize>>());
+    }
+}
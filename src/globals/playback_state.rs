@@ -4,47 +4,44 @@ use std::sync::atomic::AtomicU32;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 use std::time::Duration;
-use std::time::SystemTime;
-use std::time::UNIX_EPOCH;
 use crate::tasks::listener_updater::UpdateActions;
 use crate::types::types_msg_channels::MsgChannels;
 
 use super::terminal_state::GlobalUiState;
 
 //-////////////////////////////////////////////////////////////////////////////
-static PLAYBACK_IS_LOADING  : AtomicBool = AtomicBool::new(false);
-static PLAYBACK_PLAYING_ID  : AtomicU64  = AtomicU64 ::new(0);
-static PLAYBACK_LOADING_ID  : AtomicU64  = AtomicU64 ::new(0);
-static PLAYBACK_STATE       : AtomicU8   = AtomicU8  ::new(0);
-static PLAYBACK_SINCE_NANO  : AtomicU32  = AtomicU32 ::new(0);
-static PLAYBACK_ELAPSED_NANO: AtomicU32  = AtomicU32 ::new(0);
-static PLAYBACK_LENGTH_NANO : AtomicU32  = AtomicU32 ::new(0);
-static PLAYBACK_SINCE_SEC   : AtomicU64  = AtomicU64 ::new(0);
-static PLAYBACK_ELAPSED_SEC : AtomicU64  = AtomicU64 ::new(0);
-static PLAYBACK_LENGTH_SEC  : AtomicU64  = AtomicU64 ::new(0);
+static PLAYBACK_IS_LOADING   : AtomicBool = AtomicBool::new(false);
+static PLAYBACK_PLAYING_ID   : AtomicU64  = AtomicU64 ::new(0);
+static PLAYBACK_PLAYING_KEY  : AtomicU64  = AtomicU64 ::new(0);
+static PLAYBACK_LOADING_ID   : AtomicU64  = AtomicU64 ::new(0);
+static PLAYBACK_STATE        : AtomicU8   = AtomicU8  ::new(0);
+static PLAYBACK_POSITION_NANO: AtomicU64  = AtomicU64 ::new(0);
+static PLAYBACK_LENGTH_NANO  : AtomicU32  = AtomicU32 ::new(0);
+static PLAYBACK_LENGTH_SEC   : AtomicU64  = AtomicU64 ::new(0);
+static PLAYBACK_VOLUME       : AtomicU8   = AtomicU8  ::new(100);
+static PLAYBACK_MUTED        : AtomicBool = AtomicBool::new(false);
 //-////////////////////////////////////////////////////////////////////////////
 pub struct GlobalPlayback {}
 #[derive(Debug)]
 pub struct GlobalPlaybackSnapshot {
     pub playing : Option<u64>,
     pub loading : Option<u64>,
-    pub since   : SystemTime,
     pub elapsed : Duration,
     pub state   : PlaybackState,
     pub duration: Duration,
     pub progress: f64,
+    pub volume  : u8,
+    pub muted   : bool,
 }
 //-////////////////////////////////////////////////////////////////////////////
 //
 //-////////////////////////////////////////////////////////////////////////////
 impl GlobalPlayback {
     // -- Store -------------------------------------------
-    pub fn start_playback(track_id: u64, elapsed: Duration, length: Duration) {
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-        PLAYBACK_SINCE_SEC .store(now.as_secs(), Ordering::Relaxed);
-        PLAYBACK_SINCE_NANO.store(now.subsec_nanos(), Ordering::Relaxed);
-        PLAYBACK_ELAPSED_SEC .store(elapsed.as_secs(), Ordering::Relaxed);
-        PLAYBACK_ELAPSED_NANO.store(elapsed.subsec_nanos(), Ordering::Relaxed);
+    /// `key` identifies the queue entry, position updates from the output for any other entry are ignored
+    pub fn start_playback(track_id: u64, key: u64, elapsed: Duration, length: Duration) {
+        PLAYBACK_PLAYING_KEY.store(key, Ordering::Relaxed);
+        PLAYBACK_POSITION_NANO.store(elapsed.as_nanos() as u64, Ordering::Relaxed);
         PLAYBACK_LENGTH_SEC .store(length.as_secs(), Ordering::Relaxed);
         PLAYBACK_LENGTH_NANO.store(length.subsec_nanos(), Ordering::Relaxed);
         PLAYBACK_STATE.store(PlaybackState::Playing as u8, Ordering::Relaxed);
@@ -52,14 +49,9 @@ impl GlobalPlayback {
     }
 
     pub fn pause_playback() {
-        let state = GlobalPlayback::state();
-        if state != PlaybackState::Playing {
+        if PlaybackState::get_state() != PlaybackState::Playing {
             return;
         }
-
-        let elapsed = GlobalPlayback::elapsed();
-        PLAYBACK_ELAPSED_SEC .store(elapsed.as_secs(), Ordering::Relaxed);
-        PLAYBACK_ELAPSED_NANO.store(elapsed.subsec_nanos(), Ordering::Relaxed);
         PLAYBACK_STATE.store(PlaybackState::Paused as u8, Ordering::Relaxed);
     }
 
@@ -67,20 +59,29 @@ impl GlobalPlayback {
         if PlaybackState::get_state() != PlaybackState::Paused {
             return;
         };
-
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-        PLAYBACK_SINCE_SEC .store(now.as_secs(), Ordering::Relaxed);
-        PLAYBACK_SINCE_NANO.store(now.subsec_nanos(), Ordering::Relaxed);
         PLAYBACK_STATE.store(PlaybackState::Playing as u8, Ordering::Relaxed);
     }
 
+    /// Moves the playback position without changing the playing or paused state
+    pub fn seek_playback(elapsed: Duration) {
+        PLAYBACK_POSITION_NANO.store(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Called from the audio output with the position of the queue entry it is playing.
+    /// Based on the frames consumed so it follows buffering and suspends rather than the wall clock.
+    pub fn set_output_position(key: u64, position: Duration) {
+        if PLAYBACK_PLAYING_KEY.load(Ordering::Relaxed) != key {
+            return;
+        }
+        PLAYBACK_POSITION_NANO.store(position.as_nanos() as u64, Ordering::Relaxed);
+    }
+
     pub fn stop_playback() {
         if PlaybackState::get_state() == PlaybackState::Stopped {
             return;
         };
 
-        PLAYBACK_ELAPSED_NANO.store(0, Ordering::Relaxed);
-        PLAYBACK_ELAPSED_SEC.store(0, Ordering::Relaxed);
+        PLAYBACK_POSITION_NANO.store(0, Ordering::Relaxed);
         PLAYBACK_STATE.store(PlaybackState::Stopped as u8, Ordering::Relaxed);
     }
 
@@ -90,6 +91,12 @@ impl GlobalPlayback {
         tx.update.send(UpdateActions::LoadingTrack(track_id.is_some())).unwrap();
     }
 
+    /// volume in percent
+    pub fn set_volume(volume: u8, muted: bool) {
+        PLAYBACK_VOLUME.store(volume.min(100), Ordering::Relaxed);
+        PLAYBACK_MUTED.store(muted, Ordering::Relaxed);
+    }
+
     // -- Read --------------------------------------------
     pub fn state()   -> PlaybackState {PlaybackState::get_state()}
     pub fn playing() -> u64           {PLAYBACK_PLAYING_ID.load(Ordering::Relaxed)}
@@ -97,18 +104,9 @@ impl GlobalPlayback {
         true  => Some(PLAYBACK_LOADING_ID.load(Ordering::Relaxed)),
         false => None,
     }}
-    pub fn since() -> SystemTime {UNIX_EPOCH + Duration::new(
-        PLAYBACK_SINCE_SEC .load(Ordering::Relaxed),
-        PLAYBACK_SINCE_NANO.load(Ordering::Relaxed),
-    )}
-    pub fn elapsed() -> Duration {
-        let since = GlobalPlayback::since();
-        let elapsed = Duration::new(
-            PLAYBACK_ELAPSED_SEC .load(Ordering::Relaxed),
-            PLAYBACK_ELAPSED_NANO.load(Ordering::Relaxed),
-        );
-        elapsed + since.elapsed().unwrap()
-    }
+    pub fn elapsed() -> Duration {Duration::from_nanos(PLAYBACK_POSITION_NANO.load(Ordering::Relaxed))}
+    pub fn volume() -> u8   {PLAYBACK_VOLUME.load(Ordering::Relaxed)}
+    pub fn muted()  -> bool {PLAYBACK_MUTED.load(Ordering::Relaxed)}
     pub fn duration() -> Duration {Duration::new(
         PLAYBACK_LENGTH_SEC .load(Ordering::Relaxed),
         PLAYBACK_LENGTH_NANO.load(Ordering::Relaxed),
@@ -128,10 +126,11 @@ impl GlobalPlayback {
                 PlaybackState::Playing => Some(GlobalPlayback::playing()),
             },
             loading : GlobalPlayback::loading(),
-            since   : GlobalPlayback::since(),
             elapsed : GlobalPlayback::elapsed(),
             duration: GlobalPlayback::duration(),
             progress: GlobalPlayback::progress(),
+            volume  : GlobalPlayback::volume(),
+            muted   : GlobalPlayback::muted(),
         }
     }
 }
@@ -1,8 +1,12 @@
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicU16;
+use std::sync::atomic::AtomicU64;
 use std::sync::atomic::AtomicU8;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 use crate::ui::utils::ui_loading_icon_util::loading_icon;
 use crate::ui::utils::ui_loading_icon_util::LOADING_ICONS_LEN;
@@ -13,6 +17,11 @@ static TERM_HEIGHT     : AtomicU16   = AtomicU16  ::new(0);
 static SCANNING_LIBRARY: AtomicBool  = AtomicBool ::new(false);
 static PROGRESS_WIDTH  : AtomicUsize = AtomicUsize::new(0);
 static LOADING_INTERVAL: AtomicU8    = AtomicU8   ::new(0);
+static SCAN_FOUND      : AtomicUsize = AtomicUsize::new(0);
+static SCAN_PARSED     : AtomicUsize = AtomicUsize::new(0);
+static SCAN_FAILED     : AtomicUsize = AtomicUsize::new(0);
+static SCAN_DIRS       : AtomicUsize = AtomicUsize::new(0);
+static SCAN_START_MS   : AtomicU64   = AtomicU64  ::new(0);
 //-////////////////////////////////////////////////////////////////////////////
 /// Contains information for UI rendering
 pub struct GlobalUiState {}
@@ -24,6 +33,18 @@ pub struct GlobalUiStateSnapshot {
     pub is_scanning: bool,
     pub progress_width: usize, // The total dynamic width for the progress indicator
     pub loading_rotation: u8,
+    pub scan: ScanProgress,
+}
+
+/// File counts of the running library scan, files read from the cache count as parsed
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub struct ScanProgress {
+    pub found : usize,
+    pub parsed: usize,
+    pub failed: usize,
+    /// time left at the rate so far, None until every file has been found and one is done
+    pub eta: Option<Duration>,
 }
 //-////////////////////////////////////////////////////////////////////////////
 //
@@ -39,6 +60,36 @@ impl GlobalUiState {
         SCANNING_LIBRARY.store(is_scanning, Ordering::Relaxed);
     }
 
+    /// Resets the scan counts, called when a scan starts
+    pub fn start_scan() {
+        SCAN_FOUND .store(0, Ordering::Relaxed);
+        SCAN_PARSED.store(0, Ordering::Relaxed);
+        SCAN_FAILED.store(0, Ordering::Relaxed);
+        SCAN_DIRS  .store(0, Ordering::Relaxed);
+        SCAN_START_MS.store(unix_ms(), Ordering::Relaxed);
+    }
+
+    /// Directory queued to be listed, the number of files is only known once none are left
+    pub fn add_scan_dir() {
+        SCAN_DIRS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn finish_scan_dir() {
+        SCAN_DIRS.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn add_scan_found() {
+        SCAN_FOUND.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_scan_parsed() {
+        SCAN_PARSED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_scan_failed() {
+        SCAN_FAILED.fetch_add(1, Ordering::Relaxed);
+    }
+
     pub fn update_progress_width(width: usize) {
         PROGRESS_WIDTH.store(width, Ordering::Relaxed);
     }
@@ -66,8 +117,31 @@ impl GlobalUiState {
             is_scanning     : SCANNING_LIBRARY.load(Ordering::Relaxed),
             progress_width  : PROGRESS_WIDTH.load(Ordering::Relaxed),
             loading_rotation: LOADING_INTERVAL.load(Ordering::Relaxed),
+            scan            : GlobalUiState::scan_progress(),
         }
     }
+
+    fn scan_progress() -> ScanProgress {
+        let found  = SCAN_FOUND .load(Ordering::Relaxed);
+        let parsed = SCAN_PARSED.load(Ordering::Relaxed);
+        let failed = SCAN_FAILED.load(Ordering::Relaxed);
+        let done   = parsed + failed;
+        let is_walking = SCAN_DIRS.load(Ordering::Relaxed) > 0;
+        let elapsed_ms = unix_ms().saturating_sub(SCAN_START_MS.load(Ordering::Relaxed));
+        ScanProgress{
+            found,
+            parsed,
+            failed,
+            eta: match (is_walking, done) {
+                (true, _) | (_, 0) => None,
+                (false, 1..)       => Some(Duration::from_millis(elapsed_ms * found.saturating_sub(done) as u64 / done as u64)),
+            },
+        }
+    }
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
 }
 
 impl GlobalUiStateSnapshot {
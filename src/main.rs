@@ -13,17 +13,27 @@ mod enums {
     pub mod enum_input;
 }
 mod functions {
+    pub mod functions_data_file;
     pub mod functions_hash;
+    pub mod functions_playlist_file;
+    pub mod functions_radio;
+    pub mod functions_shuffle;
 }
 mod globals {
     pub mod playback_state;
     pub mod terminal_state;
 }
+mod sounds {
+    pub mod sound_queue;
+    pub mod sound_stream;
+}
 mod state {
     pub mod state_app;
     pub mod state_interface;
     pub mod state_library;
     pub mod state_playlist;
+    pub mod state_session;
+    pub mod state_undo;
 }
 mod tasks {
     pub mod listener_input;
@@ -33,6 +43,7 @@ mod tasks {
     pub mod listener_state;
     pub mod listener_tui;
     pub mod listener_updater;
+    pub mod listener_watcher;
 }
 mod traits {
     pub mod trait_listable;
@@ -40,6 +51,7 @@ mod traits {
 mod types {
     pub mod types_style;
     pub mod types_library_entry;
+    pub mod types_library_cache;
     pub mod types_msg_channels;
     pub mod types_tui;
 }
@@ -49,12 +61,14 @@ mod ui {
     }
     pub mod utils {
         pub mod ui_loading_icon_util;
+        pub mod ui_playlist_state_util;
         pub mod ui_text_util;
         pub mod ui_time_util;
     }
     pub mod views {
         pub mod view_library;
         pub mod view_playback;
+        pub mod view_playlist;
     }
     pub mod widgets {
         pub mod widget_playback_status;
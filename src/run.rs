@@ -3,15 +3,19 @@ use crate::tasks::listener_playback::start_playback_listener;
 use crate::tasks::listener_render_delay::start_render_delay;
 use crate::tasks::listener_scanner::start_fs_scanner_listener;
 use crate::tasks::listener_state::start_state_listener;
+use crate::tasks::listener_state::StateActions;
 use crate::tasks::listener_tui::reset_terminal;
 use crate::tasks::listener_tui::start_tui_listener;
 use crate::tasks::listener_tui::RenderActions;
 use crate::tasks::listener_updater::start_updater;
+use crate::tasks::listener_watcher::start_fs_watcher_listener;
 use crate::types::types_msg_channels::MsgChannels;
 use color_eyre::eyre::Context;
 use color_eyre::Result;
 use crossbeam_channel::bounded;
 use std::thread;
+use std::time::Duration;
+use std::time::Instant;
 
 //-////////////////////////////////////////////////////////////////////////////
 #[macro_export]
@@ -40,6 +44,7 @@ pub fn run() -> Result<()>{
     let (tx_delay   , rx_delay   ) = bounded(1);
     let (tx_tui     , rx_tui     ) = bounded(1);
     let (tx_tui_done, rx_tui_done) = bounded(0);
+    let (tx_scan    , rx_scan    ) = bounded(1);
 
     let tx = || MsgChannels{
         exit    : tx_exit.clone(),
@@ -48,6 +53,7 @@ pub fn run() -> Result<()>{
         update  : tx_update.clone(),
         delay   : tx_delay.clone(),
         tui     : tx_tui.clone(),
+        scan    : tx_scan.clone(),
     };
 
     // -- Create Threads --------------------------------------------
@@ -58,7 +64,8 @@ pub fn run() -> Result<()>{
     spawn_thread!(tx(), "render-delay"   , move |tx| start_render_delay(tx, rx_delay)           )?;
     spawn_thread!(tx(), "render-interval", move |tx| start_updater(tx, rx_update)               )?;
     spawn_thread!(tx(), "state"          , move |tx| start_state_listener(tx, rx_state)         )?;
-    spawn_thread!(tx(), "scanner"        , move |tx| start_fs_scanner_listener(tx)              )?;
+    spawn_thread!(tx(), "scanner"        , move |tx| start_fs_scanner_listener(tx, rx_scan)     )?;
+    spawn_thread!(tx(), "watcher"        , move |tx| start_fs_watcher_listener(tx)              )?;
     spawn_thread!(tx(), "input"          , move |tx| start_input_listener(tx)                   )?;
 
     // -- Wait for exit signal --------------------------------------
@@ -68,6 +75,13 @@ pub fn run() -> Result<()>{
         Ok(msg) => {
             info!("Received exit signal. Running exit procedure...");
 
+            // save session
+            let (tx_state_done, rx_state_done) = bounded(1);
+            let exit = (Instant::now(), StateActions::Exit{done: tx_state_done});
+            if tx_state.send_timeout(exit, Duration::from_secs(2)).is_ok() {
+                let _ = rx_state_done.recv_timeout(Duration::from_secs(2));
+            }
+
             // reset terminal
             let _ = tx_tui.send(RenderActions::Exit);
             let _ = rx_tui_done.recv();
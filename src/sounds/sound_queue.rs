@@ -0,0 +1,435 @@
+use crate::config::conf_playback::ConfCrossfadeCurve;
+use crate::globals::playback_state::GlobalPlayback;
+use crate::sounds::sound_stream::StreamSound;
+use crate::types::types_library_entry::TrackFile;
+use awedio::NextSample;
+use awedio::Sound;
+use crossbeam_channel::Sender;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+//-////////////////////////////////////////////////////////////////////////////
+// Returned only while nothing is queued
+const DEFAULT_CHANNEL_COUNT: u16 = 2;
+const DEFAULT_SAMPLE_RATE  : u32 = 44100;
+
+pub enum QueueEvent {
+    /// Track is done and the next queued track has taken over, `elapsed` is how far into the new track playback is
+    Ended{key: u64, elapsed: Duration},
+    /// Track was dropped because decoding failed
+    Failed{key: u64, error: String},
+}
+
+pub struct QueueEntry {
+    /// unique per queued entry, the same track can be queued more than once
+    pub key     : u64,
+    /// only set for tracks with an album title, crossfade is skipped between tracks of the same album
+    pub album   : Option<u64>,
+    pub duration: Duration,
+    /// ReplayGain multiplier for this track
+    pub gain    : f32,
+    pub sound   : StreamSound,
+    /// ended event already sent while the tail is still fading out
+    pub ended   : bool,
+}
+
+impl QueueEntry {
+    pub fn new(key: u64, track: &TrackFile, gain: f32, sound: StreamSound) -> QueueEntry {
+        QueueEntry{
+            key,
+            album: track.album_title.map(|_| track.id_album),
+            duration: track.duration,
+            gain,
+            sound,
+            ended: false,
+        }
+    }
+}
+
+struct Fade {
+    frames_total: u64,
+    frames_done : u64,
+}
+
+enum FadeFrame {
+    Mixed,
+    Paused,
+    Done,
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+/// Plays queued tracks back to back on a single output without gaps, or crossfaded when configured.
+///
+/// Lives for the whole session inside the output mixer and is modified through a `Controller`.
+/// Track transitions are reported over `tx_events` so the playback thread can follow along.
+pub struct QueueSound {
+    entries      : VecDeque<QueueEntry>,
+    channel_count: u16,
+    sample_rate  : u32,
+    channel_index: u16,
+    gain         : f32,
+    paused       : bool,
+    tx_events    : Sender<QueueEvent>,
+
+    crossfade  : Option<(Duration, ConfCrossfadeCurve)>,
+    fade       : Option<Fade>,
+    fade_frame : Vec<i16>,
+    fade_index : usize,
+    fade_buffer: Vec<i16>,
+}
+
+impl QueueSound {
+    pub fn new(tx_events: Sender<QueueEvent>, crossfade: Option<(Duration, ConfCrossfadeCurve)>) -> QueueSound {
+        QueueSound{
+            entries: VecDeque::new(),
+            channel_count: DEFAULT_CHANNEL_COUNT,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            channel_index: 0,
+            gain: 1.0,
+            paused: false,
+            tx_events,
+            crossfade,
+            fade: None,
+            fade_frame: vec![],
+            fade_index: 0,
+            fade_buffer: vec![],
+        }
+    }
+
+    pub fn push(&mut self, entry: QueueEntry) {
+        self.entries.push_back(entry);
+    }
+
+    pub fn remove(&mut self, key: u64) {
+        let index = match self.entries.iter().position(|entry| entry.key == key) {
+            None        => return,
+            Some(index) => index,
+        };
+        // removing the current track also drops whatever is still fading out
+        if self.entries.iter().take(index).all(|entry| entry.ended) {
+            self.entries.retain(|entry| !entry.ended);
+        }
+        self.entries.retain(|entry| entry.key != key);
+        if index <= 1 {
+            self.fade = None;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.fade = None;
+        self.paused = false;
+    }
+
+    /// Output silence without touching the queued tracks
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Moves the track with `key` to `position`, whatever is still fading out ahead of it is dropped
+    pub fn seek(&mut self, key: u64, position: Duration) {
+        let index = match self.entries.iter().position(|entry| entry.key == key) {
+            None        => return,
+            Some(index) => index,
+        };
+        self.entries.drain(..index);
+        self.fade = None;
+        self.fade_frame.clear();
+        self.fade_index    = 0;
+        self.channel_index = 0;
+        self.entries[0].sound.seek(position);
+
+        // an interrupted fade has already read into the next track
+        if let Some(next) = self.entries.get_mut(1) {
+            if !next.sound.position().is_zero() {
+                next.sound.seek(Duration::ZERO);
+            }
+        }
+    }
+
+    /// Linear multiplier applied to everything played
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    fn finish_front(&mut self, error: Option<String>) {
+        let entry = match self.entries.pop_front() {
+            None        => return,
+            Some(entry) => entry,
+        };
+        self.channel_index = 0;
+        let event = match (error, entry.ended) {
+            (Some(error), _    ) => QueueEvent::Failed{key: entry.key, error},
+            (None       , false) => QueueEvent::Ended{key: entry.key, elapsed: Duration::ZERO},
+            (None       , true ) => return,
+        };
+        let _ = self.tx_events.send(event);
+    }
+
+    fn start_fade(&self) -> Option<Fade> {
+        let (fade_duration, _) = self.crossfade?;
+        let outgoing = self.entries.front()?;
+        let incoming = self.entries.get(1)?;
+
+        let same_album  = outgoing.album.is_some() && outgoing.album == incoming.album;
+        let same_format = outgoing.sound.channel_count() == incoming.sound.channel_count()
+            && outgoing.sound.sample_rate() == incoming.sound.sample_rate();
+        let remaining = outgoing.duration.saturating_sub(outgoing.sound.position());
+        if same_album || !same_format || remaining.is_zero() || remaining > fade_duration {
+            return None;
+        }
+
+        Some(Fade{
+            frames_total: ((remaining.as_secs_f64() * self.sample_rate as f64) as u64).max(1),
+            frames_done : 0,
+        })
+    }
+
+    /// Mixes one frame of the outgoing and incoming track into `fade_frame`
+    fn next_fade_frame(&mut self) -> FadeFrame {
+        let (fade, curve) = match (&mut self.fade, self.crossfade) {
+            (Some(fade), Some((_, curve))) => (fade, curve),
+            _ => return FadeFrame::Done,
+        };
+
+        self.fade_frame.clear();
+        match self.entries[0].sound.append_next_frame_to(&mut self.fade_frame) {
+            Ok(())                      => {},
+            Err(Ok(NextSample::Paused)) => return FadeFrame::Paused,
+            Err(Ok(_)) | Err(Err(_))    => {
+                self.fade_frame.clear();
+                self.fade = None;
+                self.finish_front(None);
+                return FadeFrame::Done;
+            },
+        }
+
+        // an incoming buffer underrun is played as silence rather than stalling the outgoing track
+        self.fade_buffer.clear();
+        if self.entries[1].sound.append_next_frame_to(&mut self.fade_buffer).is_err() {
+            self.fade_buffer.clear();
+        }
+        self.fade_buffer.resize(self.fade_frame.len(), 0);
+
+        let progress = (fade.frames_done as f32 + 0.5) / fade.frames_total as f32;
+        let (gain_out, gain_in) = curve.gains(progress.min(1.0));
+        let (gain_out, gain_in) = (gain_out * self.entries[0].gain, gain_in * self.entries[1].gain);
+        self.fade_frame.iter_mut()
+            .zip(self.fade_buffer.iter())
+            .for_each(|(out, inc)| *out = (*out as f32 * gain_out + *inc as f32 * gain_in) as i16);
+        self.fade_index = 0;
+        fade.frames_done += 1;
+
+        // playback switches over to the incoming track at the midpoint
+        if !self.entries[0].ended && fade.frames_done * 2 >= fade.frames_total {
+            self.entries[0].ended = true;
+            let _ = self.tx_events.send(QueueEvent::Ended{
+                key    : self.entries[0].key,
+                elapsed: self.entries[1].sound.position(),
+            });
+        }
+        if fade.frames_done >= fade.frames_total {
+            self.fade = None;
+            self.entries.pop_front();
+        }
+        FadeFrame::Mixed
+    }
+
+    fn next_queued_sample(&mut self) -> Result<NextSample, awedio::Error> {
+        loop {
+            // rest of a crossfaded frame
+            if let Some(sample) = self.fade_frame.get(self.fade_index) {
+                self.fade_index += 1;
+                return Ok(NextSample::Sample(*sample));
+            }
+
+            let entry = match self.entries.front() {
+                None        => return Ok(NextSample::Paused),
+                Some(entry) => entry,
+            };
+
+            // a new track might have a different format, let the output converters catch up
+            let (channel_count, sample_rate) = (entry.sound.channel_count(), entry.sound.sample_rate());
+            if channel_count != self.channel_count || sample_rate != self.sample_rate {
+                self.channel_count = channel_count;
+                self.sample_rate   = sample_rate;
+                self.channel_index = 0;
+                return Ok(NextSample::MetadataChanged);
+            }
+
+            if self.channel_index == 0 {
+                if self.fade.is_none() {
+                    self.fade = self.start_fade();
+                }
+                if self.fade.is_some() {
+                    match self.next_fade_frame() {
+                        FadeFrame::Paused => return Ok(NextSample::Paused),
+                        FadeFrame::Mixed |
+                        FadeFrame::Done   => continue,
+                    }
+                }
+            }
+
+            match self.entries[0].sound.next_sample() {
+                Ok(NextSample::Sample(sample)) => {
+                    self.channel_index = (self.channel_index + 1) % self.channel_count;
+                    return Ok(NextSample::Sample((sample as f32 * self.entries[0].gain) as i16));
+                },
+                Ok(NextSample::MetadataChanged) => self.channel_index = 0,
+                Ok(NextSample::Paused)          => return Ok(NextSample::Paused),
+                Ok(NextSample::Finished)        => self.finish_front(None),
+                Err(err)                        => self.finish_front(Some(err.to_string())),
+            }
+        }
+    }
+}
+
+impl Sound for QueueSound {
+    fn channel_count(&self) -> u16 {
+        self.channel_count
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn next_sample(&mut self) -> Result<NextSample, awedio::Error> {
+        if self.paused {
+            return Ok(NextSample::Paused);
+        }
+        match self.next_queued_sample()? {
+            NextSample::Sample(sample) => Ok(NextSample::Sample((sample as f32 * self.gain) as i16)),
+            next => Ok(next),
+        }
+    }
+
+    fn on_start_of_batch(&mut self) {
+        if let Some(entry) = self.entries.front_mut() {
+            entry.sound.on_start_of_batch();
+        }
+        // tails still fading out are already reported as ended
+        if let Some(entry) = self.entries.iter().find(|entry| !entry.ended) {
+            GlobalPlayback::set_output_position(entry.key, entry.sound.position());
+        }
+    }
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::unbounded;
+    use crossbeam_channel::Receiver;
+    use pretty_assertions::assert_eq;
+    use std::thread;
+
+    /// ramp track at 1000 Hz so every sample is also its position in milliseconds
+    fn entry(key: u64, frames: u32) -> QueueEntry {
+        QueueEntry{
+            key,
+            album   : None,
+            duration: Duration::from_millis(frames as u64),
+            gain    : 1.0,
+            sound   : StreamSound::test_ramp(&format!("queue_{}", key), 1000, frames),
+            ended   : false,
+        }
+    }
+
+    /// Waits while a stream decoder catches up, None once nothing is queued
+    fn next(queue: &mut QueueSound) -> Option<i16> {
+        loop {
+            match queue.next_sample().unwrap() {
+                NextSample::Sample(sample)                     => return Some(sample),
+                NextSample::MetadataChanged                    => {},
+                NextSample::Paused if queue.entries.is_empty() => return None,
+                NextSample::Paused                             => thread::sleep(Duration::from_millis(1)),
+                NextSample::Finished                           => return None,
+            }
+        }
+    }
+
+    fn ended(rx: &Receiver<QueueEvent>) -> Vec<(u64, Duration)> {
+        rx.try_iter()
+            .filter_map(|event| match event {
+                QueueEvent::Ended{key, elapsed} => Some((key, elapsed)),
+                QueueEvent::Failed{..}          => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_queue_gapless_handoff() {
+        let (tx, rx) = unbounded();
+        let mut queue = QueueSound::new(tx, None);
+        queue.push(entry(1, 300));
+        queue.push(entry(2, 200));
+
+        let samples = std::iter::from_fn(|| next(&mut queue)).collect::<Vec<i16>>();
+        let ramps   = (0..300).chain(0..200).collect::<Vec<i16>>();
+        assert_eq!(samples, ramps);
+        assert_eq!(ended(&rx), vec![(1, Duration::ZERO), (2, Duration::ZERO)]);
+    }
+
+    #[test]
+    fn test_queue_seek_and_remove_current() {
+        let (tx, rx) = unbounded();
+        let mut queue = QueueSound::new(tx, None);
+        queue.push(entry(1, 300));
+        queue.push(entry(2, 200));
+        (0..10).for_each(|_| {next(&mut queue);});
+
+        queue.seek(1, Duration::from_millis(250));
+        assert_eq!(next(&mut queue), Some(250));
+
+        // the next track takes over right away, the playback thread already knows
+        queue.remove(1);
+        assert_eq!(next(&mut queue), Some(0));
+        assert_eq!(queue.entries.len(), 1);
+        assert_eq!(ended(&rx), vec![]);
+
+        // unknown keys are ignored
+        queue.seek(7, Duration::ZERO);
+        queue.remove(7);
+        assert_eq!(next(&mut queue), Some(1));
+    }
+
+    #[test]
+    fn test_queue_crossfade() {
+        let (tx, rx) = unbounded();
+        let mut queue = QueueSound::new(tx, Some((Duration::from_millis(100), ConfCrossfadeCurve::Linear)));
+        queue.push(entry(1, 300));
+        queue.push(entry(2, 300));
+
+        // the fade starts 100 ms before the end and reports the handoff halfway through
+        let mut read = 0;
+        let elapsed = loop {
+            next(&mut queue).unwrap();
+            read += 1;
+            if let Some((key, elapsed)) = ended(&rx).pop() {
+                assert_eq!(key, 1);
+                break elapsed;
+            }
+        };
+        assert!((248..=252).contains(&read), "ended after {} samples", read);
+        assert!((48..=52).contains(&elapsed.as_millis()), "ended {:?} into the next track", elapsed);
+        assert!(queue.entries[0].ended);
+        assert_eq!(queue.entries.len(), 2);
+
+        // the outgoing track is dropped once the fade is done and the next one carries on where the fade left it
+        while queue.entries.len() == 2 {
+            next(&mut queue).unwrap();
+            read += 1;
+        }
+        assert!((298..=302).contains(&read), "faded out after {} samples", read);
+        assert_eq!(queue.entries[0].key, 2);
+        let position = (queue.entries[0].sound.position().as_secs_f64() * 1000.0).round() as i16;
+        assert_eq!(next(&mut queue), Some(position));
+
+        // no second ended event for the track that faded out
+        while next(&mut queue).is_some() {}
+        assert_eq!(ended(&rx), vec![(2, Duration::ZERO)]);
+    }
+}
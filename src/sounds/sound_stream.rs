@@ -0,0 +1,411 @@
+use awedio::NextSample;
+use awedio::Sound;
+use color_eyre::eyre::Context;
+use color_eyre::eyre::OptionExt;
+use color_eyre::Result;
+use crossbeam_channel::bounded;
+use crossbeam_channel::select;
+use crossbeam_channel::unbounded;
+use crossbeam_channel::Receiver;
+use crossbeam_channel::Sender;
+use crossbeam_channel::TryRecvError;
+use std::fs::File;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::Decoder;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::codecs::CODEC_TYPE_NULL;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::formats::FormatReader;
+use symphonia::core::formats::SeekMode;
+use symphonia::core::formats::SeekTo;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::Limit;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+//-////////////////////////////////////////////////////////////////////////////
+/// Decoded packets kept ahead of playback. Bounds memory use independent of track length.
+const STREAM_BUFFER_CHUNKS: usize = 32;
+
+enum StreamChunk {
+    Samples{generation: u64, channel_count: u16, sample_rate: u32, samples: Vec<i16>},
+    End{generation: u64},
+    Error{generation: u64, error: String},
+}
+
+enum StreamCommand {
+    Seek{generation: u64, position: Duration},
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+/// Sound that decodes a file incrementally on a separate thread.
+///
+/// Only a bounded number of decoded packets are buffered, so memory use does not grow with track length.
+/// Reads never block the audio thread, an empty buffer is reported as `Paused` until the decoder catches up.
+pub struct StreamSound {
+    channel_count: u16,
+    sample_rate  : u32,
+    generation   : u64,
+    seeked_to    : Duration,
+    samples_read : u64,
+    samples      : Vec<i16>,
+    index        : usize,
+    finished     : bool,
+    rx_chunks    : Receiver<StreamChunk>,
+    tx_commands  : Sender<StreamCommand>,
+}
+
+impl StreamSound {
+    /// Probes the file and decodes the first packet before returning, the rest is decoded in the background.
+    pub fn open(path: &Path) -> Result<StreamSound> {
+        let mut decoder = StreamDecoder::open(path)?;
+        let (channel_count, sample_rate, samples) = decoder.next_chunk()?
+            .ok_or_eyre("Track contains no audio")?;
+
+        let (tx_chunks  , rx_chunks  ) = bounded(STREAM_BUFFER_CHUNKS);
+        let (tx_commands, rx_commands) = unbounded();
+        thread::Builder::new()
+            .name("stream-decoder".to_string())
+            .spawn(move || decoder.decode_loop(tx_chunks, rx_commands))
+            .context("Starting stream decoder thread")?;
+
+        Ok(StreamSound{
+            channel_count,
+            sample_rate,
+            generation: 0,
+            seeked_to: Duration::ZERO,
+            samples_read: 0,
+            samples,
+            index: 0,
+            finished: false,
+            rx_chunks,
+            tx_commands,
+        })
+    }
+
+    /// Moves the read position. Buffered samples from before the seek are discarded.
+    pub fn seek(&mut self, position: Duration) {
+        self.generation += 1;
+        self.seeked_to = position;
+        self.samples_read = 0;
+        self.samples.clear();
+        self.index = 0;
+        self.finished = false;
+        let _ = self.tx_commands.send(StreamCommand::Seek{generation: self.generation, position});
+    }
+
+    /// Read position within the track
+    pub fn position(&self) -> Duration {
+        let frames = self.samples_read as f64 / self.channel_count.max(1) as f64;
+        self.seeked_to + Duration::from_secs_f64(frames / self.sample_rate.max(1) as f64)
+    }
+}
+
+impl Sound for StreamSound {
+    fn channel_count(&self) -> u16 {
+        self.channel_count
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn next_sample(&mut self) -> Result<NextSample, awedio::Error> {
+        while self.index >= self.samples.len() {
+            if self.finished {
+                return Ok(NextSample::Finished);
+            }
+            match self.rx_chunks.try_recv() {
+                Err(TryRecvError::Empty)        => return Ok(NextSample::Paused),
+                Err(TryRecvError::Disconnected) => {
+                    self.finished = true;
+                    return Ok(NextSample::Finished);
+                },
+                Ok(StreamChunk::Samples{generation, channel_count, sample_rate, samples}) => {
+                    if generation != self.generation {
+                        continue;
+                    }
+                    self.samples = samples;
+                    self.index   = 0;
+                    if channel_count != self.channel_count || sample_rate != self.sample_rate {
+                        self.channel_count = channel_count;
+                        self.sample_rate   = sample_rate;
+                        return Ok(NextSample::MetadataChanged);
+                    }
+                },
+                Ok(StreamChunk::End{generation}) => {
+                    if generation == self.generation {
+                        self.finished = true;
+                    }
+                },
+                Ok(StreamChunk::Error{generation, error}) => {
+                    if generation == self.generation {
+                        self.finished = true;
+                        return Err(awedio::Error::IoError(std::io::Error::other(error)));
+                    }
+                },
+            }
+        }
+
+        let sample = self.samples[self.index];
+        self.index += 1;
+        self.samples_read += 1;
+        Ok(NextSample::Sample(sample))
+    }
+
+    fn on_start_of_batch(&mut self) {}
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+struct StreamDecoder {
+    format     : Box<dyn FormatReader>,
+    decoder    : Box<dyn Decoder>,
+    track_id   : u32,
+    buffer     : Option<SampleBuffer<i16>>,
+    skip_frames: u64,
+    ended      : bool,
+}
+
+impl StreamDecoder {
+    fn open(path: &Path) -> Result<StreamDecoder> {
+        let file = File::open(path).context(format!("Opening audio file {}", path.to_string_lossy()))?;
+        let mss  = MediaSourceStream::new(Box::new(file), Default::default());
+
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+            hint.with_extension(extension);
+        }
+        let metadata = MetadataOptions{
+            limit_metadata_bytes: Limit::Maximum(1),
+            limit_visual_bytes  : Limit::Maximum(1),
+        };
+        let probed = symphonia::default::get_probe()
+            .format(&hint, mss, &FormatOptions::default(), &metadata)
+            .context("Probing audio format")?;
+
+        let format = probed.format;
+        let track = format.tracks().iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_eyre("No track with a supported codec")?;
+        let track_id = track.id;
+        let decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .context("Creating audio decoder")?;
+
+        Ok(StreamDecoder{
+            format,
+            decoder,
+            track_id,
+            buffer: None,
+            skip_frames: 0,
+            ended: false,
+        })
+    }
+
+    /// (channels, sample rate, interleaved samples), None at end of stream
+    fn next_chunk(&mut self) -> Result<Option<(u16, u32, Vec<i16>)>> {
+        loop {
+            if self.ended {
+                return Ok(None);
+            }
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(err)) if err.kind() == ErrorKind::UnexpectedEof => {
+                    self.ended = true;
+                    return Ok(None);
+                },
+                Err(SymphoniaError::ResetRequired) => {
+                    self.decoder.reset();
+                    continue;
+                },
+                Err(err) => return Err(err.into()),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(SymphoniaError::DecodeError(err)) => {
+                    warn!("Skipping undecodable packet: {}", err);
+                    continue;
+                },
+                Err(SymphoniaError::ResetRequired) => {
+                    self.decoder.reset();
+                    continue;
+                },
+                Err(err) => return Err(err.into()),
+            };
+
+            let spec     = *decoded.spec();
+            let frames   = decoded.frames();
+            let channels = spec.channels.count();
+            if frames == 0 {
+                continue;
+            }
+
+            let buffer = match &mut self.buffer {
+                Some(buffer) if buffer.capacity() >= frames * channels => buffer,
+                _ => self.buffer.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+            };
+            buffer.copy_interleaved_ref(decoded);
+
+            // drop frames between the seeked packet start and the requested position
+            let skip = (self.skip_frames as usize).min(frames);
+            self.skip_frames -= skip as u64;
+            if skip == frames {
+                continue;
+            }
+
+            let samples = buffer.samples()[skip * channels..].to_vec();
+            return Ok(Some((channels as u16, spec.rate, samples)));
+        }
+    }
+
+    fn seek(&mut self, position: Duration) {
+        self.ended = false;
+        self.skip_frames = 0;
+        let res = self.format.seek(SeekMode::Accurate, SeekTo::Time{time: position.into(), track_id: Some(self.track_id)});
+        match res {
+            Ok(seeked) => self.skip_frames = seeked.required_ts.saturating_sub(seeked.actual_ts),
+            Err(err) => {
+                // most often a seek past the end of the track
+                debug!("Seek to {:?} failed: {}", position, err);
+                self.ended = true;
+            },
+        }
+        self.decoder.reset();
+    }
+
+    fn decode_loop(mut self, tx: Sender<StreamChunk>, rx: Receiver<StreamCommand>) {
+        let mut generation = 0;
+        loop {
+            // apply commands received while decoding
+            loop {
+                match rx.try_recv() {
+                    Ok(StreamCommand::Seek{generation: new, position}) => {
+                        generation = new;
+                        self.seek(position);
+                    },
+                    Err(TryRecvError::Empty)        => break,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            }
+
+            let chunk = match self.next_chunk() {
+                Ok(Some((channel_count, sample_rate, samples))) => {
+                    StreamChunk::Samples{generation, channel_count, sample_rate, samples}
+                },
+                Ok(None) => StreamChunk::End{generation},
+                Err(err) => StreamChunk::Error{generation, error: format!("{:?}", err)},
+            };
+            let is_last = !matches!(chunk, StreamChunk::Samples{..});
+
+            // wait for buffer space, a seek makes the pending chunk stale
+            select! {
+                send(tx, chunk) -> res => if res.is_err() {
+                    return;
+                },
+                recv(rx) -> command => match command {
+                    Err(_) => return,
+                    Ok(StreamCommand::Seek{generation: new, position}) => {
+                        generation = new;
+                        self.seek(position);
+                        continue;
+                    },
+                },
+            }
+
+            // idle until seeked back into the track or dropped
+            if is_last {
+                match rx.recv() {
+                    Err(_) => return,
+                    Ok(StreamCommand::Seek{generation: new, position}) => {
+                        generation = new;
+                        self.seek(position);
+                    },
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl StreamSound {
+    /// Mono 16 bit pcm wav where each sample is its own index
+    pub fn write_ramp_wav(path: &Path, sample_rate: u32, frames: u32) {
+        use std::io::Write;
+        let data_len = frames * 2;
+        let mut wav = Vec::with_capacity(44 + data_len as usize);
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        (0..frames).for_each(|i| wav.extend_from_slice(&(i as i16).to_le_bytes()));
+        File::create(path).unwrap().write_all(&wav).unwrap();
+    }
+
+    /// Ramp from `write_ramp_wav` opened as a stream, the file is gone once the decoder has it open
+    pub fn test_ramp(name: &str, sample_rate: u32, frames: u32) -> StreamSound {
+        let path = std::env::temp_dir().join(format!("music_player_{}_{}.wav", name, std::process::id()));
+        StreamSound::write_ramp_wav(&path, sample_rate, frames);
+        let sound = StreamSound::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        sound
+    }
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn read_until_finished(sound: &mut StreamSound) -> Vec<i16> {
+        let mut samples = vec![];
+        loop {
+            match sound.next_sample().unwrap() {
+                NextSample::Sample(sample)   => samples.push(sample),
+                NextSample::Paused           => thread::sleep(Duration::from_millis(1)),
+                NextSample::MetadataChanged  => {},
+                NextSample::Finished         => return samples,
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream_sound_read_and_seek() {
+        let path = std::env::temp_dir().join(format!("music_player_stream_{}.wav", std::process::id()));
+        StreamSound::write_ramp_wav(&path, 1000, 20_000);
+
+        let mut sound = StreamSound::open(&path).unwrap();
+        assert_eq!(sound.channel_count(), 1);
+        assert_eq!(sound.sample_rate(), 1000);
+        assert_eq!(read_until_finished(&mut sound), (0..20_000).map(|i| i as i16).collect::<Vec<i16>>());
+
+        sound.seek(Duration::from_secs(15));
+        assert_eq!(read_until_finished(&mut sound), (15_000..20_000).map(|i| i as i16).collect::<Vec<i16>>());
+
+        sound.seek(Duration::from_secs(30));
+        assert_eq!(read_until_finished(&mut sound), vec![]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
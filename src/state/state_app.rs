@@ -1,11 +1,13 @@
 use crate::globals::playback_state::GlobalPlayback;
 use crate::globals::terminal_state::GlobalUiState;
+use crate::state::state_interface::CurrentView;
 use crate::state::state_interface::StateInterface;
 use crate::state::state_library::StateLibrary;
 use crate::state::state_playlist::StatePlaylist;
 use crate::tasks::listener_tui::RenderDataCommon;
 use crate::tasks::listener_tui::RenderDataView;
 use crate::ui::views::view_library::RenderDataViewLibrary;
+use crate::ui::views::view_playlist::RenderDataViewPlaylist;
 use crate::CONFIG;
 
 //-////////////////////////////////////////////////////////////////////////////
@@ -43,6 +45,10 @@ impl AppState {
         self.has_changed = true;
     }
 
+    pub fn playlist(&self) -> &StatePlaylist {
+        &self.playlist
+    }
+
     pub fn render_state(&mut self) -> Option<(RenderDataCommon, RenderDataView)> {
         self.has_changed = false;
 
@@ -54,24 +60,45 @@ impl AppState {
             theme: CONFIG.get().unwrap().theme,
             term,
             playback,
-            playlist: self.playlist.clone(),
+            playlist: self.playlist.states(),
+            status: self.interface.status(),
+            command: self.interface.command,
         };
 
 
         let list_height = term.height.saturating_sub(2) as usize;
-        let (left ,  left_selected) = self.library.list_filter.view(list_height);
-        let (right, right_selected) = self.library.list_tracks.view(list_height);
+        let view = match self.interface.current_view {
+            CurrentView::Library => {
+                let (left ,  left_selected) = self.library.list_filter.view(list_height);
+                let (right, right_selected) = self.library.list_tracks.view(list_height);
 
-        let view = RenderDataView::Library(RenderDataViewLibrary{
-            column_selected  : self.library.selected_column,
-            tab_selected     : self.library.selected_tab,
-            track_select_mode: self.library.select_mode,
-            list_height,
-            left,
-            left_selected,
-            right,
-            right_selected,
-        });
+                RenderDataView::Library(RenderDataViewLibrary{
+                    column_selected  : self.library.selected_column,
+                    tab_selected     : self.library.selected_tab,
+                    track_select_mode: self.library.select_mode,
+                    list_height,
+                    left,
+                    left_selected,
+                    right,
+                    right_selected,
+                    broken: self.library.broken.keys().copied().collect(),
+                })
+            },
+            CurrentView::Playlist => {
+                let (rows, cursor)     = self.playlist.view(list_height);
+                let (total, remaining) = common.playlist.durations(common.playback.elapsed);
+
+                RenderDataView::Playlist(RenderDataViewPlaylist{
+                    list_height,
+                    rows,
+                    cursor,
+                    track_count: self.playlist.list.len(),
+                    total,
+                    remaining,
+                    broken: self.library.broken.keys().copied().collect(),
+                })
+            },
+        };
 
         Some((common, view))
     }
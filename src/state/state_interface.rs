@@ -1,3 +1,7 @@
+use arrayvec::ArrayString;
+use std::time::Duration;
+use std::time::Instant;
+
 //-////////////////////////////////////////////////////////////////////////////
 //
 //-////////////////////////////////////////////////////////////////////////////
@@ -5,20 +9,54 @@
 #[derive(Debug)]
 pub struct StateInterface {
     pub current_view: CurrentView,
+    /// transient message shown in place of the playback progress
+    pub status: Option<StatusMessage>,
+    /// command being typed, shown in place of the playback status
+    pub command: Option<ArrayString<256>>,
+}
+
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+pub struct StatusMessage {
+    pub text : ArrayString<128>,
+    pub until: Instant,
 }
 
 #[derive(Clone, Copy)]
 #[derive(Debug)]
 pub enum CurrentView {
     Library,
+    Playlist,
 }
 
 impl StateInterface {
     pub fn init() -> StateInterface {
         StateInterface{
             current_view: CurrentView::Library,
+            status: None,
+            command: None,
         }
     }
+
+    pub fn set_status(&mut self, text: &str, duration: Duration) {
+        let mut message = ArrayString::new();
+        for c in text.chars() {
+            if message.try_push(c).is_err() {
+                break;
+            }
+        }
+        self.status = Some(StatusMessage{
+            text : message,
+            until: Instant::now() + duration,
+        });
+    }
+
+    /// Message text while it has not expired
+    pub fn status(&self) -> Option<ArrayString<128>> {
+        self.status
+            .filter(|status| status.until > Instant::now())
+            .map(|status| status.text)
+    }
 }
 //-////////////////////////////////////////////////////////////////////////////
 //
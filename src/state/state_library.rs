@@ -2,11 +2,21 @@ use crate::enums::enum_input::InputEffect;
 use crate::enums::enum_input::InputGlobalEffect;
 use crate::enums::enum_input::InputLocal;
 use crate::enums::enum_input::InputLocalEffect;
+use crate::functions::functions_playlist_file::PlaylistFileEntry;
 use crate::traits::trait_listable::Listable;
+use crate::types::types_library_entry::LibraryAlbumEntry;
 use crate::types::types_library_entry::LibraryArtistEntry;
 use crate::types::types_library_entry::LibraryFilterEntry;
+use crate::types::types_library_entry::str_trunc;
 use crate::types::types_library_entry::TrackFile;
 use crate::ui::models::model_component_list_state::SortedListState;
+use arrayvec::ArrayString;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::time::SystemTime;
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 use strum_macros::IntoStaticStr;
@@ -19,26 +29,36 @@ use strum_macros::IntoStaticStr;
 pub struct StateLibrary {
     pub tracks: Vec<TrackFile>,
     pub filters_artist: Vec<LibraryFilterEntry>,
+    pub filters_albums: Vec<LibraryFilterEntry>,
     pub filters_years: Vec<LibraryFilterEntry>,
+    pub filters_genre: Vec<LibraryFilterEntry>,
     pub selected_tab: LibraryTab,
     pub selected_column: LibraryColumn,
     pub select_mode: LibrarySelectMode,
     pub list_filter: SortedListState<LibraryFilterEntry>,
     pub list_tracks: SortedListState<TrackFile>,
+    /// tracks that failed to play, with the modified time of the file at the time
+    pub broken: BTreeMap<u64, Option<SystemTime>>,
+    /// file path of every scanned track
+    pub paths: BTreeMap<u64, Box<Path>>,
 }
 
 #[derive(Clone, Copy)]
 #[derive(Debug)]
 #[derive(PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
 #[derive(EnumIter, IntoStaticStr)]
 pub enum LibraryTab {
     Artists,
+    Albums,
     Year,
+    Genre,
 }
 
 #[derive(Clone, Copy)]
 #[derive(Debug)]
 #[derive(PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
 pub enum LibraryColumn {
     Filter,
     Tracks,
@@ -47,6 +67,7 @@ pub enum LibraryColumn {
 #[derive(Clone, Copy)]
 #[derive(Debug)]
 #[derive(PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
 #[derive(EnumIter, IntoStaticStr)]
 pub enum LibrarySelectMode {
     All,
@@ -62,12 +83,16 @@ impl StateLibrary {
         StateLibrary{
             tracks: vec![],
             filters_artist: vec![LibraryFilterEntry::All],
+            filters_albums: vec![LibraryFilterEntry::All],
             filters_years: vec![LibraryFilterEntry::All],
+            filters_genre: vec![LibraryFilterEntry::All],
             selected_tab: LibraryTab::Artists,
             selected_column: LibraryColumn::Filter,
             select_mode: LibrarySelectMode::Artist,
             list_filter: filter,
             list_tracks: SortedListState::new(false),
+            broken: BTreeMap::new(),
+            paths: BTreeMap::new(),
         }
     }
 
@@ -87,54 +112,56 @@ impl StateLibrary {
             InputLocal::End    => local(InputLocalEffect::End),
             InputLocal::Tab    => local(InputLocalEffect::NextTab),
             InputLocal::RevTab => local(InputLocalEffect::PrevTab),
+            InputLocal::MoveUp      |
+            InputLocal::MoveDown    |
+            InputLocal::Remove      |
+            InputLocal::ClearPlayed => InputEffect::None,
             InputLocal::Select => match self.selected_column {
                 LibraryColumn::Filter => local(InputLocalEffect::Right),
-                LibraryColumn::Tracks => {
-                    let entry = match self.list_tracks.selected_entry() {
-                        None => return InputEffect::None,
-                        Some(entry) => entry,
-                    };
-                    let tracks = self.list_tracks.entries().iter().copied()
-                        .filter(|track| track.is_selectable())
-                        .filter(|track| match self.select_mode {
-                            LibrarySelectMode::All    => true,
-                            LibrarySelectMode::Artist => track.id_artist == entry.id_artist,
-                            LibrarySelectMode::Album  => track.id_album  == entry.id_album,
-                            LibrarySelectMode::Track  => track.id_track  == entry.id_track,
-                        })
-                        .collect::<Vec<TrackFile>>();
-                    let index = tracks.iter()
-                        .enumerate()
-                        .find_map(|(index, track)| match track.id_track == entry.id_track {
-                            false => None,
-                            true  => Some(index),
-                        })
-                        .unwrap_or(0);
-                    global(InputGlobalEffect::ReplaceTracksAndPlay{tracks, index})
+                LibraryColumn::Tracks => match self.selected_tracks() {
+                    None => InputEffect::None,
+                    Some((tracks, index)) => global(InputGlobalEffect::ReplaceTracksAndPlay{tracks, index}),
                 },
             },
             InputLocal::SelectAlt => match self.selected_column {
                 LibraryColumn::Filter => local(InputLocalEffect::Right),
-                LibraryColumn::Tracks => {
-                    let entry = match self.list_tracks.selected_entry() {
-                        None => return InputEffect::None,
-                        Some(entry) => entry,
-                    };
-                    let tracks = self.list_tracks.entries().iter().copied()
-                        .filter(|track| track.is_selectable())
-                        .filter(|track| match self.select_mode {
-                            LibrarySelectMode::All    => true,
-                            LibrarySelectMode::Artist => track.id_artist == entry.id_artist,
-                            LibrarySelectMode::Album  => track.id_album  == entry.id_album,
-                            LibrarySelectMode::Track  => track.id_track  == entry.id_track,
-                        })
-                        .collect::<Vec<TrackFile>>();
-                    global(InputGlobalEffect::AppendTracks(tracks))
+                LibraryColumn::Tracks => match self.selected_tracks() {
+                    None => InputEffect::None,
+                    Some((tracks, _)) => global(InputGlobalEffect::AppendTracks(tracks)),
+                },
+            },
+            InputLocal::SelectNext => match self.selected_column {
+                LibraryColumn::Filter => local(InputLocalEffect::Right),
+                LibraryColumn::Tracks => match self.selected_tracks() {
+                    None => InputEffect::None,
+                    Some((tracks, _)) => global(InputGlobalEffect::InsertTracksNext(tracks)),
                 },
             },
         }
     }
 
+    /// Tracks covered by the select mode around the selected track, and the index of the selected track among them
+    fn selected_tracks(&self) -> Option<(Vec<TrackFile>, usize)> {
+        let entry = self.list_tracks.selected_entry()?;
+        let tracks = self.list_tracks.entries().iter().copied()
+            .filter(|track| track.is_selectable())
+            .filter(|track| match self.select_mode {
+                LibrarySelectMode::All    => true,
+                LibrarySelectMode::Artist => track.id_artist == entry.id_artist,
+                LibrarySelectMode::Album  => track.id_album  == entry.id_album,
+                LibrarySelectMode::Track  => track.id_track  == entry.id_track,
+            })
+            .collect::<Vec<TrackFile>>();
+        let index = tracks.iter()
+            .enumerate()
+            .find_map(|(index, track)| match track.id_track == entry.id_track {
+                false => None,
+                true  => Some(index),
+            })
+            .unwrap_or(0);
+        Some((tracks, index))
+    }
+
     pub fn handle_input_effect(&mut self, effect: InputLocalEffect) {
         match self.selected_column {
             LibraryColumn::Filter => {
@@ -191,21 +218,134 @@ impl StateLibrary {
         }
     }
 
+    /// Restores a saved view, the filter entry is matched by name
+    pub fn restore_view(
+        &mut self,
+        tab: LibraryTab,
+        column: LibraryColumn,
+        select_mode: LibrarySelectMode,
+        filter: Option<&str>,
+        track_id: Option<u64>,
+    ) {
+        self.selected_tab = tab;
+        self.select_mode  = select_mode;
+        self.refresh_filter_list();
+        if let Some(filter) = filter {
+            self.list_filter.select_where(|entry| entry.name().as_str() == filter);
+        }
+        self.refresh_tracks_list();
+        if let Some(track_id) = track_id {
+            self.list_tracks.select_where(|track| track.is_selectable() && track.id_track == track_id);
+        }
+        self.selected_column = column;
+    }
+
+    // -- Playlist Files ------------------------------------------------------
+
+    /// Scanned tracks for playlist file entries, in order, and the entries that have no scanned track
+    pub fn tracks_for_playlist_file(&self, entries: Vec<PlaylistFileEntry>) -> (Vec<TrackFile>, Vec<PlaylistFileEntry>) {
+        let by_id = self.tracks.iter()
+            .map(|track| (track.id_track, *track))
+            .collect::<BTreeMap<u64, TrackFile>>();
+        // built on the first entry that is not found by path
+        let mut by_title = None;
+        let mut tracks  = Vec::with_capacity(entries.len());
+        let mut missing = vec![];
+        for entry in entries {
+            let track = match by_id.get(&TrackFile::id_for_path(&entry.path)) {
+                Some(track) => Some(*track),
+                None => StateLibrary::track_for_tags(by_title.get_or_insert_with(|| self.tracks_by_title()), &entry),
+            };
+            match track {
+                Some(track) => tracks.push(track),
+                None        => missing.push(entry),
+            }
+        }
+        (tracks, missing)
+    }
+
+    /// Tracks by lowercase title, for looking up playlist file entries by their tags
+    fn tracks_by_title(&self) -> BTreeMap<String, Vec<TrackFile>> {
+        let mut by_title = BTreeMap::<String, Vec<TrackFile>>::new();
+        for track in self.tracks.iter() {
+            by_title.entry(track.track_title.to_lowercase()).or_default().push(*track);
+        }
+        by_title
+    }
+
+    /// Fallback for entries whose file moved, matches title and whichever of artist and album the entry has.
+    /// Entry tags are cut to the length the library keeps before they are compared.
+    fn track_for_tags(by_title: &BTreeMap<String, Vec<TrackFile>>, entry: &PlaylistFileEntry) -> Option<TrackFile> {
+        let title = entry.title.as_deref()?;
+        let matches = |tag: Option<ArrayString<64>>, value: &Option<String>| match value {
+            None        => true,
+            Some(value) => tag.is_some_and(|tag| tag.to_lowercase() == tag_key::<64>(value)),
+        };
+        by_title.get(&tag_key::<128>(title))?
+            .iter()
+            .find(|track| {
+                (matches(track.track_artist, &entry.artist) || matches(track.album_artist, &entry.artist))
+                && matches(track.album_title, &entry.album)
+            })
+            .copied()
+    }
+
+    /// Playlist file entries for tracks, tracks without a known path are left out
+    pub fn playlist_file_entries(&self, tracks: &[TrackFile]) -> Vec<PlaylistFileEntry> {
+        tracks.iter()
+            .filter_map(|track| Some(PlaylistFileEntry{
+                path    : self.paths.get(&track.id_track)?.to_path_buf(),
+                title   : Some(track.track_title.to_string()),
+                artist  : track.track_artist.or(track.album_artist).map(|artist| artist.to_string()),
+                album   : track.album_title.map(|album| album.to_string()),
+                duration: Some(track.duration),
+            }))
+            .collect()
+    }
+
     // -- Mutate Data ---------------------------------------------------------
 
+    /// Broken until the file changes
+    pub fn mark_broken(&mut self, track_id: u64) {
+        let modified = self.tracks.iter()
+            .find(|track| track.id_track == track_id)
+            .and_then(|track| track.modified);
+        self.broken.insert(track_id, modified);
+    }
+
+    pub fn is_broken(&self, track_id: u64) -> bool {
+        self.broken.contains_key(&track_id)
+    }
+
     pub fn new_track(&mut self, track: TrackFile) {
+        if self.broken.get(&track.id_track).is_some_and(|modified| *modified != track.modified) {
+            self.broken.remove(&track.id_track);
+        }
+
         // add to filter lists
         let artist = LibraryFilterEntry::Artist(LibraryArtistEntry::from_track(track));
+        let album  = LibraryFilterEntry::Album(LibraryAlbumEntry::from_track(track));
         let year   = LibraryFilterEntry::Year{year: track.year};
         if let Err(index) = self.filters_artist.binary_search(&artist) {
             self.filters_artist.insert(index, artist);
         }
+        if let Err(index) = self.filters_albums.binary_search(&album) {
+            self.filters_albums.insert(index, album);
+        }
         if let Err(index) = self.filters_years.binary_search(&year) {
             self.filters_years.insert(index, year);
         }
+        let genres = track.genre_entries().into_iter().map(LibraryFilterEntry::Genre).collect::<Vec<LibraryFilterEntry>>();
+        for genre in genres.iter() {
+            if let Err(index) = self.filters_genre.binary_search(genre) {
+                self.filters_genre.insert(index, *genre);
+            }
+        }
         match self.selected_tab {
             LibraryTab::Artists => self.list_filter.add(artist),
+            LibraryTab::Albums  => self.list_filter.add(album),
             LibraryTab::Year    => self.list_filter.add(year),
+            LibraryTab::Genre   => genres.into_iter().for_each(|genre| self.list_filter.add(genre)),
         };
 
         // add to track list
@@ -213,7 +353,9 @@ impl StateLibrary {
             None |
             Some(LibraryFilterEntry::All           ) => true,
             Some(LibraryFilterEntry::Artist(artist)) => artist.artist_id == track.id_artist,
+            Some(LibraryFilterEntry::Album(album)  ) => album.album_id == track.id_album,
             Some(LibraryFilterEntry::Year{year}    ) => *year == track.year,
+            Some(LibraryFilterEntry::Genre(genre)  ) => track.has_genre(genre.genre_id),
         };
         if add_to_track_list {
             if !self.list_tracks.entries().iter().any(|e| e.album_title == track.album_title) {
@@ -230,11 +372,99 @@ impl StateLibrary {
         }
     }
 
+    /// Replaces a track whose file changed, the track moves to where its new tags sort
+    pub fn update_track(&mut self, track: TrackFile) {
+        let index = match self.track_index(&track) {
+            Some(index) => index,
+            None => return self.new_track(track),
+        };
+        let is_selected = self.list_tracks.selected_entry()
+            .is_some_and(|entry| entry.is_selectable() && entry.id_track == track.id_track);
+        let old = self.tracks.remove(index);
+        self.remove_from_view(&[old]);
+        self.new_track(track);
+        if is_selected {
+            self.list_tracks.select_where(|entry| entry.is_selectable() && entry.id_track == track.id_track);
+        }
+    }
+
+    /// Index of the track in `tracks`, searched where it sorts first as changed files mostly keep their tags
+    fn track_index(&self, track: &TrackFile) -> Option<usize> {
+        let start = self.tracks.partition_point(|other| other < track);
+        self.tracks[start..].iter()
+            .take_while(|other| other.cmp(&track).is_eq())
+            .position(|other| other.id_track == track.id_track)
+            .map(|index| start + index)
+            .or_else(|| self.tracks.iter().position(|other| other.id_track == track.id_track))
+    }
+
+    /// Removes tracks whose files are gone, filter entries without tracks go with them
+    pub fn remove_tracks(&mut self, track_ids: &BTreeSet<u64>) {
+        let mut removed = vec![];
+        self.tracks.retain(|track| match track_ids.contains(&track.id_track) {
+            true  => {removed.push(*track); false},
+            false => true,
+        });
+        removed.iter().for_each(|track| {self.broken.remove(&track.id_track);});
+        self.remove_from_view(&removed);
+    }
+
+    /// Takes tracks that are no longer in `tracks` out of the filters and lists
+    fn remove_from_view(&mut self, removed: &[TrackFile]) {
+        if removed.is_empty() {
+            return;
+        }
+
+        // filters, only entries of the removed tracks can be left without tracks
+        let mut artists = removed.iter().map(|track| track.id_artist).collect::<BTreeSet<u64>>();
+        let mut albums  = removed.iter().map(|track| track.id_album).collect::<BTreeSet<u64>>();
+        let mut years   = removed.iter().map(|track| track.year).collect::<BTreeSet<Option<u16>>>();
+        let mut genres  = removed.iter().flat_map(|track| track.genre_filter_ids()).collect::<BTreeSet<u64>>();
+        for track in self.tracks.iter() {
+            if artists.is_empty() && albums.is_empty() && years.is_empty() && genres.is_empty() {
+                break;
+            }
+            artists.remove(&track.id_artist);
+            albums .remove(&track.id_album);
+            years  .remove(&track.year);
+            track.genre_filter_ids().for_each(|genre_id| {genres.remove(&genre_id);});
+        }
+        let is_empty = |entry: &LibraryFilterEntry| match entry {
+            LibraryFilterEntry::All            => false,
+            LibraryFilterEntry::Artist(artist) => artists.contains(&artist.artist_id),
+            LibraryFilterEntry::Album(album)   => albums .contains(&album.album_id),
+            LibraryFilterEntry::Year{year}     => years  .contains(year),
+            LibraryFilterEntry::Genre(genre)   => genres .contains(&genre.genre_id),
+        };
+        self.filters_artist.retain(|entry| !is_empty(entry));
+        self.filters_albums.retain(|entry| !is_empty(entry));
+        self.filters_years .retain(|entry| !is_empty(entry));
+        self.filters_genre .retain(|entry| !is_empty(entry));
+
+        // filter list, a removed selection shows the tracks of the filter that is selected instead
+        let filter = self.list_filter.selected_entry().copied();
+        self.list_filter.remove_where(is_empty);
+        if self.list_filter.selected_entry().copied() != filter {
+            return self.refresh_tracks_list();
+        }
+
+        // track list, album headers go with the last track of their album
+        let removed_ids = removed.iter().map(|track| track.id_track).collect::<BTreeSet<u64>>();
+        self.list_tracks.remove_where(|entry| !entry.is_album_padding && removed_ids.contains(&entry.id_track));
+        let album_titles = self.list_tracks.entries().iter()
+            .filter(|entry| !entry.is_album_padding)
+            .map(|entry| entry.album_title)
+            .collect::<BTreeSet<Option<ArrayString<64>>>>();
+        self.list_tracks.remove_where(|entry| entry.is_album_padding && !album_titles.contains(&entry.album_title));
+    }
+
     /// full refresh of filter list
     fn refresh_filter_list(&mut self) {
         match self.selected_tab {
             LibraryTab::Artists => self.list_filter.replace_all(self.filters_artist.clone()),
+            LibraryTab::Albums  => self.list_filter.replace_all(self.filters_albums.clone()),
             LibraryTab::Year    => self.list_filter.replace_all(self.filters_years.clone()),
+            LibraryTab::Genre   => self.list_filter.replace_all(self.filters_genre.clone()),
         }
     }
 
@@ -246,7 +476,9 @@ impl StateLibrary {
                 None |
                 Some(LibraryFilterEntry::All          ) => true,
                 Some(LibraryFilterEntry::Artist(album)) => album.artist_id == track.id_artist,
+                Some(LibraryFilterEntry::Album(album) ) => album.album_id == track.id_album,
                 Some(LibraryFilterEntry::Year{year}   ) => *year == track.year,
+                Some(LibraryFilterEntry::Genre(genre) ) => track.has_genre(genre.genre_id),
             })
             .for_each(|track: TrackFile| match track.track_number {
                 Some(1) => {
@@ -260,6 +492,84 @@ impl StateLibrary {
         self.list_tracks.replace_all(tracks);
     }
 }
+
+/// Tag as the library keeps it, cut to `L` bytes and lowercase
+fn tag_key<const L: usize>(tag: &str) -> String {
+    str_trunc::<L>(tag).to_lowercase()
+}
 //-////////////////////////////////////////////////////////////////////////////
 //
 //-////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use pretty_assertions::assert_eq;
+
+    fn track(id: u64, artist: &str, album: &str, number: u8) -> TrackFile {
+        TrackFile{year: Some(2000), track_number: Some(number), ..TrackFile::test(id)}.with_album(artist, album)
+    }
+
+    #[test]
+    fn test_library_tracks_for_playlist_file_tags() {
+        let long_title = "Ä".repeat(100);
+        let mut library = StateLibrary::init();
+        library.new_track(TrackFile{track_title: str_trunc(&long_title), ..track(1, "Ärzte", "Album", 1)});
+        library.new_track(TrackFile{track_title: ArrayString::from("Other").unwrap(), ..track(2, "Ärzte", "Album", 2)});
+
+        let entry = |title: &str, artist: &str| PlaylistFileEntry{
+            path    : PathBuf::from("/moved.flac"),
+            title   : Some(title.to_string()),
+            artist  : Some(artist.to_string()),
+            album   : None,
+            duration: None,
+        };
+        let (tracks, missing) = library.tracks_for_playlist_file(vec![
+            entry(&long_title.to_lowercase(), "ÄRZTE"),
+            entry("OTHER", "ärzte"),
+            entry("Other", "someone else"),
+        ]);
+        assert_eq!(tracks.iter().map(|track| track.id_track).collect::<Vec<u64>>(), vec![1, 2]);
+        assert_eq!(missing.len(), 1);
+    }
+
+    #[test]
+    fn test_library_remove_and_update() {
+        let rows = |library: &StateLibrary| library.list_tracks.entries().iter()
+            .map(|track| (track.id_track, track.is_album_padding))
+            .collect::<Vec<(u64, bool)>>();
+        let mut library = StateLibrary::init();
+        library.new_track(track(1, "a", "x", 1));
+        library.new_track(track(2, "a", "x", 2));
+        library.new_track(track(3, "b", "y", 1));
+        assert_eq!(rows(&library), vec![(1, true), (1, false), (2, false), (3, true), (3, false)]);
+
+        // the selection moves on and the album header and artist go with the last track
+        library.list_tracks.select_where(|track| track.is_selectable() && track.id_track == 3);
+        library.remove_tracks(&BTreeSet::from([3]));
+        assert_eq!(rows(&library), vec![(1, true), (1, false), (2, false)]);
+        assert_eq!(library.list_tracks.selected_entry().map(|track| track.id_track), Some(2));
+        assert_eq!(library.filters_artist.len(), 2);
+        assert_eq!(library.list_filter.len(), 2);
+
+        // changed tags sort the track again and keep it selected
+        library.update_track(track(2, "a", "x", 0));
+        assert_eq!(rows(&library), vec![(1, true), (2, false), (1, false)]);
+        assert_eq!(library.list_tracks.selected_entry().map(|track| track.id_track), Some(2));
+
+        // an artist stays listed until its last track is retagged
+        library.update_track(track(2, "c", "z", 1));
+        assert_eq!(library.filters_artist.len(), 3);
+        library.update_track(track(1, "c", "z", 2));
+        assert_eq!(library.filters_artist.len(), 2);
+        assert_eq!(library.filters_albums.len(), 2);
+
+        library.remove_tracks(&BTreeSet::from([1, 2]));
+        assert_eq!(rows(&library), vec![]);
+        assert_eq!(library.filters_artist, vec![LibraryFilterEntry::All]);
+        assert_eq!(library.filters_albums, vec![LibraryFilterEntry::All]);
+        assert_eq!(library.filters_years , vec![LibraryFilterEntry::All]);
+        assert_eq!(library.filters_genre , vec![LibraryFilterEntry::All]);
+    }
+}
@@ -1,5 +1,15 @@
+use crate::functions::functions_shuffle::random_index;
+use crate::functions::functions_shuffle::shuffle;
 use crate::types::types_library_entry::LibraryFilterEntry;
 use crate::types::types_library_entry::TrackFile;
+use serde::Deserialize;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
 
 //-//////////////////////////////////////////////////////////////////
 //
@@ -7,12 +17,69 @@ use crate::types::types_library_entry::TrackFile;
 #[derive(Clone)]
 #[derive(Debug)]
 pub struct StatePlaylist {
-    pub list: Vec<TrackFile>,
+    pub list: Vec<PlaylistEntry>,
     pub selected: usize,
+    pub repeat: PlaylistRepeat,
+    pub shuffle: bool,
+    /// related tracks are appended before the queue runs out
+    pub radio: bool,
+    /// indexes played before the current track, most recent last
+    history: Vec<usize>,
+    /// upcoming indexes while shuffled, next first
+    order: VecDeque<usize>,
+    /// highlighted entry in the playlist view
+    pub cursor: usize,
+    scroll_anchor: usize,
+    /// id for the next entry
+    next_id: u64,
+    /// rebuilt by every method that changes the queue or the position in it
+    states: Arc<PlaylistStates>,
 }
 
+/// Playlist state per entry, track, artist and year, shared with the renderer without copying the queue
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct PlaylistStates {
+    pub repeat : PlaylistRepeat,
+    pub shuffle: bool,
+    pub radio  : bool,
+    entries: Vec<PlaylistState>,
+    tracks : BTreeMap<u64, PlaylistState>,
+    artists: BTreeMap<u64, PlaylistState>,
+    albums : BTreeMap<u64, PlaylistState>,
+    years  : BTreeMap<Option<u16>, PlaylistState>,
+    genres : BTreeMap<u64, PlaylistState>,
+    /// duration of the whole queue
+    total  : Duration,
+    /// duration of the current track
+    current: Duration,
+    /// duration of everything queued after the current track
+    queued : Duration,
+}
+
+/// Queue entry, the same track can be queued more than once and every entry has its own id
 #[derive(Clone, Copy)]
 #[derive(Debug)]
+#[derive(PartialEq, Eq)]
+pub struct PlaylistEntry {
+    pub id   : u64,
+    pub track: TrackFile,
+}
+
+/// Queue and the position in it, what undo puts back while the modes and cursor stay as they are
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct PlaylistQueue {
+    pub list   : Vec<PlaylistEntry>,
+    selected   : usize,
+    history    : Vec<usize>,
+    order      : VecDeque<usize>,
+    is_shuffled: bool,
+}
+
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+#[derive(PartialEq, Eq)]
 pub enum PlaylistState {
     None,
     Played,
@@ -20,92 +87,713 @@ pub enum PlaylistState {
     Queued,
 }
 
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+#[derive(PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum PlaylistRepeat {
+    Off,
+    One,
+    All,
+}
+
+impl PlaylistState {
+    /// Most relevant of two states, for tracks, artists, albums and years with more than one entry
+    fn merge(self, other: PlaylistState) -> PlaylistState {
+        match (self, other) {
+            (PlaylistState::Playing, _) | (_, PlaylistState::Playing) => PlaylistState::Playing,
+            (PlaylistState::Queued , _) | (_, PlaylistState::Queued ) => PlaylistState::Queued,
+            (PlaylistState::Played , _) | (_, PlaylistState::Played ) => PlaylistState::Played,
+            (_, _) => PlaylistState::None,
+        }
+    }
+}
+
+impl PlaylistStates {
+    fn init() -> PlaylistStates {
+        PlaylistStates{
+            repeat : PlaylistRepeat::Off,
+            shuffle: false,
+            radio  : false,
+            entries: vec![],
+            tracks : BTreeMap::new(),
+            artists: BTreeMap::new(),
+            albums : BTreeMap::new(),
+            years  : BTreeMap::new(),
+            genres : BTreeMap::new(),
+            total  : Duration::ZERO,
+            current: Duration::ZERO,
+            queued : Duration::ZERO,
+        }
+    }
+
+    /// (total, remaining) duration with `elapsed` into the current track
+    pub fn durations(&self, elapsed: Duration) -> (Duration, Duration) {
+        (self.total, self.queued + self.current.saturating_sub(elapsed))
+    }
+
+    pub fn get_playback_state_for_filter(&self, entry: LibraryFilterEntry) -> PlaylistState {
+        let state = match entry {
+            LibraryFilterEntry::All            => None,
+            LibraryFilterEntry::Artist(artist) => self.artists.get(&artist.artist_id),
+            LibraryFilterEntry::Album(album)   => self.albums.get(&album.album_id),
+            LibraryFilterEntry::Year{year}     => self.years.get(&year),
+            LibraryFilterEntry::Genre(genre)   => self.genres.get(&genre.genre_id),
+        };
+        state.copied().unwrap_or(PlaylistState::None)
+    }
+
+    /// A track queued more than once shows the most relevant state of its entries
+    pub fn get_playback_state_for_track(&self, track_id: u64) -> PlaylistState {
+        self.tracks.get(&track_id).copied().unwrap_or(PlaylistState::None)
+    }
+}
+
 impl StatePlaylist {
     pub fn init() -> StatePlaylist {
         StatePlaylist{
             list: vec![],
             selected: 0,
+            repeat: PlaylistRepeat::Off,
+            shuffle: false,
+            radio: false,
+            history: vec![],
+            order: VecDeque::new(),
+            cursor: 0,
+            scroll_anchor: 0,
+            next_id: 0,
+            states: Arc::new(PlaylistStates::init()),
         }
     }
 
+    fn track(&self, index: usize) -> Option<&TrackFile> {
+        self.list.get(index).map(|entry| &entry.track)
+    }
+
+    fn new_entries(&mut self, tracks: Vec<TrackFile>) -> Vec<PlaylistEntry> {
+        tracks.into_iter()
+            .map(|track| {
+                self.next_id += 1;
+                PlaylistEntry{id: self.next_id, track}
+            })
+            .collect()
+    }
+
     pub fn get_current_track(&self) -> Option<TrackFile> {
-        self.list.get(self.selected).copied()
+        self.track(self.selected).copied()
+    }
+
+    /// Index of the track that plays after the current one, skipping tracks `is_playable` rejects
+    pub fn get_next_index(&self, is_playable: impl Fn(&TrackFile) -> bool) -> Option<usize> {
+        self.next_index(is_playable, false)
+    }
+
+    /// Repeat one only holds on to the current track until the user skips it, then it moves on like repeat all
+    fn next_index(&self, is_playable: impl Fn(&TrackFile) -> bool, user_skip: bool) -> Option<usize> {
+        let playable = |index: &usize| self.track(*index).is_some_and(&is_playable);
+        if self.repeat == PlaylistRepeat::One && !user_skip && playable(&self.selected) {
+            return Some(self.selected);
+        }
+        match (self.shuffle, self.repeat) {
+            (true , _) => self.order.iter().copied().find(playable),
+            (false, PlaylistRepeat::All | PlaylistRepeat::One) => (self.selected+1..self.list.len())
+                .chain(0..=self.selected.min(self.list.len().saturating_sub(1)))
+                .find(playable),
+            (false, _) => (self.selected+1..self.list.len()).find(playable),
+        }
+    }
+
+    pub fn get_next_track(&self, is_playable: impl Fn(&TrackFile) -> bool) -> Option<TrackFile> {
+        self.get_next_index(is_playable).and_then(|index| self.track(index).copied())
     }
 
-    pub fn get_next_track(&self) -> Option<TrackFile> {
-        self.list.get(self.selected+1).copied()
+    /// Track at `index` is played together with the rest of its album, used for ReplayGain album mode
+    pub fn is_album_run(&self, index: usize) -> bool {
+        let track = match self.track(index) {
+            Some(track) if track.album_title.is_some() => track,
+            _ => return false,
+        };
+        let same_album = |other: Option<&TrackFile>| other.map(|other| other.id_album == track.id_album).unwrap_or(false);
+        same_album(index.checked_sub(1).and_then(|index| self.track(index))) || same_album(self.track(index+1))
     }
 
-    pub fn next(&mut self) {
-        self.selected += 1;
+    /// Moves on to the track from `get_next_index`, or past the end of the list when there is none
+    pub fn next(&mut self, is_playable: impl Fn(&TrackFile) -> bool) {
+        self.advance(is_playable, false);
     }
 
-    pub fn previous(&mut self) {
-        match self.selected {
-            0 => {},
-            _ => self.selected -= 1,
+    /// Moves on when the user skips the current track, this leaves it even when it is repeated
+    pub fn skip(&mut self, is_playable: impl Fn(&TrackFile) -> bool) {
+        self.advance(is_playable, true);
+    }
+
+    fn advance(&mut self, is_playable: impl Fn(&TrackFile) -> bool, user_skip: bool) {
+        let next = self.next_index(is_playable, user_skip);
+        if next != Some(self.selected) && self.selected < self.list.len() {
+            self.history.push(self.selected);
+        }
+        if let Some(position) = next.and_then(|next| self.order.iter().position(|index| *index == next)) {
+            self.order.drain(..=position);
         }
+        self.selected = next.unwrap_or(self.list.len());
+
+        let is_repeated = self.repeat == PlaylistRepeat::All || (user_skip && self.repeat == PlaylistRepeat::One);
+        if self.shuffle && self.order.is_empty() && is_repeated {
+            self.reshuffle(false);
+        }
+        self.update_states();
     }
 
-    pub fn replace(&mut self, list: Vec<TrackFile>, selected: usize) {
-        self.list = list;
+    /// Goes back through the tracks that were actually played
+    pub fn previous(&mut self, is_playable: impl Fn(&TrackFile) -> bool) {
+        while let Some(index) = self.history.pop() {
+            if !self.track(index).is_some_and(&is_playable) {
+                continue;
+            }
+            if self.shuffle && self.selected < self.list.len() {
+                self.order.push_front(self.selected);
+            }
+            self.selected = index;
+            self.update_states();
+            return;
+        }
+        if self.shuffle {
+            return;
+        }
+        let previous = self.list.iter()
+            .take(self.selected)
+            .rposition(|entry| is_playable(&entry.track));
+        if let Some(index) = previous {
+            self.selected = index;
+            self.update_states();
+        }
+    }
+
+    /// Moves forward until the current track is accepted by `is_playable`
+    pub fn skip_unplayable(&mut self, is_playable: impl Fn(&TrackFile) -> bool) {
+        if self.track(self.selected).is_some_and(|track| !is_playable(track)) {
+            self.next(is_playable);
+        }
+    }
+
+    pub fn cycle_repeat(&mut self) {
+        self.repeat = match self.repeat {
+            PlaylistRepeat::Off => PlaylistRepeat::All,
+            PlaylistRepeat::All => PlaylistRepeat::One,
+            PlaylistRepeat::One => PlaylistRepeat::Off,
+        };
+        if self.shuffle && self.order.is_empty() && self.repeat == PlaylistRepeat::All {
+            self.reshuffle(false);
+        }
+        self.update_states();
+    }
+
+    /// Turning shuffle off continues in list order from the current track
+    pub fn toggle_shuffle(&mut self) {
+        self.shuffle = !self.shuffle;
+        match self.shuffle {
+            true  => self.reshuffle(true),
+            false => self.order.clear(),
+        }
+        self.update_states();
+    }
+
+    pub fn toggle_radio(&mut self) {
+        self.radio = !self.radio;
+        self.update_states();
+    }
+
+    /// Up to `limit` tracks, the current one first and then back through what was played
+    pub fn recent_tracks(&self, limit: usize) -> Vec<TrackFile> {
+        std::iter::once(self.selected)
+            .chain(self.history.iter().rev().copied())
+            .filter_map(|index| self.track(index).copied())
+            .take(limit)
+            .collect()
+    }
+
+    /// Tracks still to be played after the current one
+    pub fn queued_tracks(&self) -> Vec<TrackFile> {
+        self.list.iter()
+            .zip(self.states.entries.iter())
+            .filter(|(_, state)| matches!(state, PlaylistState::Queued))
+            .map(|(entry, _)| entry.track)
+            .collect()
+    }
+
+    /// New random order of everything except the current track, and optionally except what has been played
+    fn reshuffle(&mut self, skip_played: bool) {
+        let unplayed = |index: &usize| !skip_played || !self.history.contains(index);
+        let mut order = (0..self.list.len())
+            .filter(|index| *index != self.selected)
+            .filter(unplayed)
+            .collect::<Vec<usize>>();
+        if order.is_empty() && skip_played && self.repeat == PlaylistRepeat::All {
+            order = (0..self.list.len()).filter(|index| *index != self.selected).collect();
+        }
+        shuffle(&mut order);
+        self.order = order.into();
+    }
+
+    fn update_states(&mut self) {
+        let order = match self.shuffle {
+            true  => self.order.iter().copied().collect(),
+            false => BTreeSet::new(),
+        };
+        let state_for_index = |index: usize| match (index == self.selected, self.shuffle) {
+            (true , _    ) => PlaylistState::Playing,
+            (false, true ) => match order.contains(&index) {
+                true  => PlaylistState::Queued,
+                false => PlaylistState::Played,
+            },
+            (false, false) => match index < self.selected {
+                true  => PlaylistState::Played,
+                false => PlaylistState::Queued,
+            },
+        };
+
+        let mut states = PlaylistStates::init();
+        states.repeat  = self.repeat;
+        states.shuffle = self.shuffle;
+        states.radio   = self.radio;
+        for (index, entry) in self.list.iter().enumerate() {
+            let state = state_for_index(index);
+            let track = entry.track;
+            states.entries.push(state);
+            states.tracks .entry(track.id_track ).and_modify(|s| *s = s.merge(state)).or_insert(state);
+            states.artists.entry(track.id_artist).and_modify(|s| *s = s.merge(state)).or_insert(state);
+            states.albums .entry(track.id_album ).and_modify(|s| *s = s.merge(state)).or_insert(state);
+            states.years  .entry(track.year     ).and_modify(|s| *s = s.merge(state)).or_insert(state);
+            for genre_id in track.genre_filter_ids() {
+                states.genres.entry(genre_id).and_modify(|s| *s = s.merge(state)).or_insert(state);
+            }
+            states.total += track.duration;
+            match state {
+                PlaylistState::Playing => states.current = track.duration,
+                PlaylistState::Queued  => states.queued += track.duration,
+                PlaylistState::Played |
+                PlaylistState::None    => {},
+            }
+        }
+        self.states = Arc::new(states);
+    }
+
+    pub fn states(&self) -> Arc<PlaylistStates> {
+        self.states.clone()
+    }
+
+    pub fn replace(&mut self, tracks: Vec<TrackFile>, selected: usize) {
+        self.list = self.new_entries(tracks);
         self.selected = selected;
+        self.history.clear();
+        self.order.clear();
+        if self.shuffle {
+            self.reshuffle(false);
+        }
+        self.update_states();
     }
 
-    pub fn append(&mut self, track: TrackFile) {
-        self.list.push(track);
+    /// Adds `tracks` to the end, while shuffled they are spread over the upcoming order at random
+    pub fn append(&mut self, tracks: Vec<TrackFile>) {
+        let start   = self.list.len();
+        let entries = self.new_entries(tracks);
+        self.list.extend(entries);
+        if self.shuffle {
+            let mut added = (start..self.list.len()).collect::<Vec<usize>>();
+            shuffle(&mut added);
+            let mut positions = added.iter()
+                .map(|_| random_index(self.order.len() + 1))
+                .collect::<Vec<usize>>();
+            positions.sort();
+
+            let mut added = positions.into_iter().zip(added).peekable();
+            let mut order = VecDeque::with_capacity(self.order.len() + added.len());
+            for (position, index) in self.order.iter().enumerate() {
+                while let Some((_, added)) = added.next_if(|(at, _)| *at <= position) {
+                    order.push_back(added);
+                }
+                order.push_back(*index);
+            }
+            order.extend(added.map(|(_, index)| index));
+            self.order = order;
+        }
+        self.update_states();
+    }
+
+    /// Inserts `tracks` to play right after the current track, also while shuffled
+    pub fn insert_next(&mut self, tracks: Vec<TrackFile>) {
+        let index = (self.selected + 1).min(self.list.len());
+        let count   = tracks.len();
+        let entries = self.new_entries(tracks);
+        self.list.splice(index..index, entries);
+        self.remap(|i| match i >= index {
+            true  => Some(i + count),
+            false => Some(i),
+        });
+        if self.shuffle {
+            (index..index+count)
+                .rev()
+                .filter(|i| *i != self.selected)
+                .for_each(|i| self.order.push_front(i));
+        }
+        self.update_states();
     }
 
     pub fn clear(&mut self) {
         self.list.clear();
         self.selected = 0;
+        self.history.clear();
+        self.order.clear();
+        self.cursor = 0;
+        self.update_states();
     }
 
-    pub fn get_playback_state_for_filter(&self, entry: LibraryFilterEntry) -> PlaylistState {
-        match entry {
-            LibraryFilterEntry::All => PlaylistState::None,
-            LibraryFilterEntry::Artist(artist) => self.list.iter()
-                .enumerate()
-                .filter(|(_, track)| track.id_artist == artist.artist_id)
-                .map(|(index, _)| match index as isize - self.selected as isize {
-                    ..0 => PlaylistState::Played,
-                     0  => PlaylistState::Playing,
-                    1.. => PlaylistState::Queued,
-                })
-                .fold(PlaylistState::None, |acc, state| match (acc, state) {
-                    (PlaylistState::Playing, _) | (_, PlaylistState::Playing) => PlaylistState::Playing,
-                    (PlaylistState::Queued , _) | (_, PlaylistState::Queued ) => PlaylistState::Queued,
-                    (PlaylistState::Played , _) | (_, PlaylistState::Played ) => PlaylistState::Played,
-                    (_, _) => PlaylistState::None,
-                }),
-            LibraryFilterEntry::Year{year} => self.list.iter()
-                .enumerate()
-                .filter(|(_, track)| track.year == year)
-                .map(|(index, _)| match index as isize - self.selected as isize {
-                    ..0 => PlaylistState::Played,
-                     0  => PlaylistState::Playing,
-                    1.. => PlaylistState::Queued,
-                })
-                .fold(PlaylistState::None, |acc, state| match (acc, state) {
-                    (PlaylistState::Playing, _) | (_, PlaylistState::Playing) => PlaylistState::Playing,
-                    (PlaylistState::Queued , _) | (_, PlaylistState::Queued ) => PlaylistState::Queued,
-                    (PlaylistState::Played , _) | (_, PlaylistState::Played ) => PlaylistState::Played,
-                    (_, _) => PlaylistState::None,
-                }),
+    // -- Edit ----------------------------------------------------------------
+
+    /// Maps stored indexes after entries moved or were removed, `None` drops the index
+    fn remap(&mut self, map: impl Fn(usize) -> Option<usize>) {
+        self.history = self.history.iter().filter_map(|index| map(*index)).collect();
+        self.order   = self.order.iter().filter_map(|index| map(*index)).collect();
+    }
+
+    /// Swaps the entry at `index` with its neighbour, the cursor follows the entry
+    pub fn move_entry(&mut self, index: usize, up: bool) {
+        let other = match up {
+            true  => index.checked_sub(1),
+            false => Some(index+1).filter(|other| *other < self.list.len()),
+        };
+        let other = match other {
+            Some(other) if index < self.list.len() => other,
+            _ => return,
+        };
+        self.list.swap(index, other);
+        let swap = |i: usize| match i {
+            i if i == index => other,
+            i if i == other => index,
+            i => i,
+        };
+        self.selected = swap(self.selected);
+        self.remap(|i| Some(swap(i)));
+        self.cursor = other;
+        self.update_states();
+    }
+
+    /// Removing the current track makes the following entry current, or the next shuffled one
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.list.len() {
+            return;
+        }
+        self.list.remove(index);
+        let shift = |i: usize| match i.cmp(&index) {
+            Ordering::Less    => Some(i),
+            Ordering::Equal   => None,
+            Ordering::Greater => Some(i-1),
+        };
+        let was_current = self.selected == index;
+        if self.selected > index {
+            self.selected -= 1;
         }
+        self.remap(shift);
+        if was_current && self.shuffle {
+            self.selected = self.order.pop_front().unwrap_or(self.list.len());
+        }
+        self.cursor = self.cursor.min(self.list.len().saturating_sub(1));
+        self.update_states();
     }
 
-    pub fn get_playback_state_for_track(&self, track_id: u64) -> PlaylistState {
-        match self.list.iter().enumerate().find(|(_, track)| track.id_track == track_id) {
-            Some((index, _)) => match index as isize - self.selected as isize {
-                ..0 => PlaylistState::Played,
-                 0  => PlaylistState::Playing,
-                1.. => PlaylistState::Queued,
-            },
-            None => PlaylistState::None,
+    /// Removes every entry of tracks whose files are gone, except the current entry which is left to finish playing
+    pub fn remove_tracks(&mut self, track_ids: &BTreeSet<u64>) {
+        let removed = self.list.iter()
+            .map(|entry| track_ids.contains(&entry.track.id_track))
+            .collect::<Vec<bool>>();
+        self.remove_entries(removed);
+        self.update_states();
+    }
+
+    /// Removes the entries marked in `removed` in one pass, the current entry is always kept
+    fn remove_entries(&mut self, mut removed: Vec<bool>) {
+        if let Some(current) = removed.get_mut(self.selected) {
+            *current = false;
+        }
+        if !removed.contains(&true) {
+            return;
+        }
+        // new index of every entry that is kept
+        let mut kept = 0;
+        let map = removed.iter()
+            .map(|is_removed| match is_removed {
+                true  => None,
+                false => {kept += 1; Some(kept - 1)},
+            })
+            .collect::<Vec<Option<usize>>>();
+
+        let mut index = 0;
+        self.list.retain(|_| {index += 1; !removed[index - 1]});
+        self.selected = map.get(self.selected).copied().flatten().unwrap_or(self.list.len());
+        self.remap(|i| map.get(i).copied().flatten());
+        self.cursor = self.cursor.min(self.list.len().saturating_sub(1));
+    }
+
+    /// Gives every entry of a track the tags of its changed file, false when the track is not in the playlist
+    pub fn update_track(&mut self, track: TrackFile) -> bool {
+        let mut is_listed = false;
+        self.list.iter_mut()
+            .filter(|entry| entry.track.id_track == track.id_track)
+            .for_each(|entry| {entry.track = track; is_listed = true;});
+        if is_listed {
+            self.update_states();
         }
+        is_listed
+    }
+
+    /// Removes everything that has already been played
+    pub fn clear_played(&mut self) {
+        let played = self.states.entries.iter()
+            .map(|state| matches!(state, PlaylistState::Played))
+            .collect::<Vec<bool>>();
+        self.remove_entries(played);
+        self.history.clear();
+        self.update_states();
+    }
+
+    /// Makes `index` the current track, the previous current track goes to the history
+    pub fn jump(&mut self, index: usize) {
+        if index >= self.list.len() || index == self.selected {
+            return;
+        }
+        if self.selected < self.list.len() {
+            self.history.push(self.selected);
+        }
+        self.order.retain(|i| *i != index);
+        self.selected = index;
+        self.update_states();
+    }
+
+    // -- View ----------------------------------------------------------------
+
+    pub fn cursor_up(&mut self, steps: usize) {
+        self.cursor = self.cursor.saturating_sub(steps);
+    }
+
+    pub fn cursor_down(&mut self, steps: usize) {
+        self.cursor = (self.cursor + steps).min(self.list.len().saturating_sub(1));
+    }
+
+    pub fn cursor_start(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn cursor_end(&mut self) {
+        self.cursor = self.list.len().saturating_sub(1);
+    }
+
+    /// Visible rows with their playlist index and state, and the cursor position within them
+    pub fn view(&mut self, height: usize) -> (Vec<(PlaylistEntry, PlaylistState)>, usize) {
+        let padding = 2.min(height / 2);
+        if self.cursor < self.scroll_anchor + padding {
+            self.scroll_anchor = self.cursor.saturating_sub(padding);
+        }
+        if self.cursor + padding + 1 > self.scroll_anchor + height {
+            self.scroll_anchor = (self.cursor + padding + 1).saturating_sub(height);
+        }
+        self.scroll_anchor = self.scroll_anchor.min(self.list.len().saturating_sub(height));
+
+        let rows = self.list.iter()
+            .enumerate()
+            .skip(self.scroll_anchor)
+            .take(height)
+            .map(|(index, entry)| (*entry, self.states.entries[index]))
+            .collect();
+        (rows, self.cursor.saturating_sub(self.scroll_anchor))
+    }
+
+    pub fn has_played(&self) -> bool {
+        self.states.entries.contains(&PlaylistState::Played)
+    }
+
+    pub fn queue(&self) -> PlaylistQueue {
+        PlaylistQueue{
+            list       : self.list.clone(),
+            selected   : self.selected,
+            history    : self.history.clone(),
+            order      : self.order.clone(),
+            is_shuffled: self.shuffle,
+        }
+    }
+
+    /// Puts back a queue from the undo history, ids handed out since stay used
+    pub fn restore(&mut self, queue: PlaylistQueue) {
+        self.list     = queue.list;
+        self.selected = queue.selected;
+        self.history  = queue.history;
+        self.order    = queue.order;
+        // shuffle was turned on since, the queue has no order yet
+        if self.shuffle && !queue.is_shuffled {
+            self.reshuffle(true);
+        }
+        self.cursor = self.cursor.min(self.list.len().saturating_sub(1));
+        self.update_states();
     }
 }
 //-//////////////////////////////////////////////////////////////////
 //
 //-//////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn playlist(len: u64) -> StatePlaylist {
+        let mut playlist = StatePlaylist::init();
+        playlist.replace((0..len).map(TrackFile::test).collect(), 0);
+        playlist
+    }
+
+    #[test]
+    fn test_playlist_shuffle_and_previous() {
+        let all = |_: &TrackFile| true;
+        let mut playlist = playlist(10);
+        playlist.toggle_shuffle();
+
+        let mut played = vec![playlist.selected];
+        while playlist.get_current_track().is_some() {
+            let next = playlist.get_next_index(all);
+            playlist.next(all);
+            assert_eq!(next.unwrap_or(playlist.list.len()), playlist.selected);
+            played.push(playlist.selected);
+        }
+        played.pop();
+        let mut sorted = played.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..10).collect::<Vec<usize>>());
+
+        // previous walks back through the shuffled order
+        playlist.previous(all);
+        assert_eq!(playlist.selected, played[9]);
+        playlist.previous(all);
+        assert_eq!(playlist.selected, played[8]);
+        playlist.previous(all);
+        assert_eq!(playlist.selected, played[7]);
+        assert_eq!(playlist.get_next_index(all), Some(played[8]));
+
+        // unshuffled playback continues in list order
+        playlist.toggle_shuffle();
+        assert_eq!(playlist.get_next_index(all), Some(played[7] + 1).filter(|index| *index < 10));
+    }
+
+    #[test]
+    fn test_playlist_repeat() {
+        let not_two = |track: &TrackFile| track.id_track != 2;
+        let mut playlist = playlist(3);
+        playlist.selected = 1;
+
+        assert_eq!(playlist.get_next_index(not_two), None);
+        playlist.cycle_repeat();
+        assert_eq!(playlist.repeat, PlaylistRepeat::All);
+        assert_eq!(playlist.get_next_index(not_two), Some(0));
+        playlist.cycle_repeat();
+        assert_eq!(playlist.repeat, PlaylistRepeat::One);
+        playlist.next(not_two);
+        assert_eq!(playlist.selected, 1);
+
+        // skipping leaves a repeated track and wraps around like repeat all
+        playlist.skip(not_two);
+        assert_eq!(playlist.selected, 0);
+        assert_eq!(playlist.get_next_index(not_two), Some(0));
+    }
+
+    #[test]
+    fn test_playlist_edit() {
+        let all = |_: &TrackFile| true;
+        let ids = |playlist: &StatePlaylist| playlist.list.iter().map(|entry| entry.track.id_track).collect::<Vec<u64>>();
+        let mut playlist = playlist(5);
+        playlist.next(all);
+        playlist.next(all);
+        assert_eq!(playlist.selected, 2);
+
+        playlist.move_entry(2, true);
+        assert_eq!(ids(&playlist), vec![0, 2, 1, 3, 4]);
+        assert_eq!(playlist.selected, 1);
+        assert_eq!(playlist.cursor, 1);
+
+        playlist.remove(3);
+        assert_eq!(ids(&playlist), vec![0, 2, 1, 4]);
+        assert_eq!(playlist.get_current_track().map(|track| track.id_track), Some(2));
+        assert_eq!(
+            playlist.states().durations(Duration::from_secs(15)),
+            (Duration::from_secs(240), Duration::from_secs(165)),
+        );
+
+        playlist.clear_played();
+        assert_eq!(ids(&playlist), vec![2, 1, 4]);
+        assert_eq!(playlist.selected, 0);
+
+        playlist.jump(2);
+        assert_eq!(playlist.get_current_track().map(|track| track.id_track), Some(4));
+        playlist.previous(all);
+        assert_eq!(playlist.selected, 0);
+
+        playlist.remove(0);
+        assert_eq!(ids(&playlist), vec![1, 4]);
+        assert_eq!(playlist.get_current_track().map(|track| track.id_track), Some(1));
+
+        // undo puts back the queue and keeps the modes that were changed since
+        let queue = playlist.queue();
+        playlist.remove(1);
+        playlist.cycle_repeat();
+        playlist.restore(queue);
+        assert_eq!(ids(&playlist), vec![1, 4]);
+        assert_eq!(playlist.repeat, PlaylistRepeat::All);
+    }
+
+    #[test]
+    fn test_playlist_insert_next() {
+        let all = |_: &TrackFile| true;
+        let mut playlist = playlist(4);
+        playlist.next(all);
+        playlist.insert_next(vec![TrackFile::test(10), TrackFile::test(11)]);
+        let ids = playlist.list.iter().map(|entry| entry.track.id_track).collect::<Vec<u64>>();
+        assert_eq!(ids, vec![0, 1, 10, 11, 2, 3]);
+        assert_eq!(playlist.get_next_track(all).map(|track| track.id_track), Some(10));
+
+        playlist.toggle_shuffle();
+        playlist.insert_next(vec![TrackFile::test(12)]);
+        assert_eq!(playlist.get_next_track(all).map(|track| track.id_track), Some(12));
+        playlist.previous(all);
+        assert_eq!(playlist.get_current_track().map(|track| track.id_track), Some(0));
+    }
+
+    #[test]
+    fn test_playlist_duplicates() {
+        let all = |_: &TrackFile| true;
+        let mut playlist = StatePlaylist::init();
+        playlist.replace(vec![TrackFile::test(1), TrackFile::test(2), TrackFile::test(1)], 0);
+        assert_ne!(playlist.list[0].id, playlist.list[2].id);
+
+        playlist.next(all);
+        playlist.next(all);
+        assert_eq!(playlist.states().get_playback_state_for_track(1), PlaylistState::Playing);
+        assert_eq!(playlist.states().get_playback_state_for_track(2), PlaylistState::Played);
+
+        playlist.jump(1);
+        assert_eq!(playlist.states().get_playback_state_for_track(1), PlaylistState::Queued);
+        assert_eq!(playlist.states().get_playback_state_for_filter(LibraryFilterEntry::Year{year: None}), PlaylistState::Playing);
+        assert_eq!(playlist.states().get_playback_state_for_filter(LibraryFilterEntry::All), PlaylistState::None);
+
+        playlist.remove(0);
+        assert_eq!(playlist.states().get_playback_state_for_track(1), PlaylistState::Queued);
+        assert_eq!(playlist.get_current_track().map(|track| track.id_track), Some(2));
+
+        // the current entry of a removed file is left to finish
+        playlist.append(vec![TrackFile::test(2)]);
+        playlist.remove_tracks(&BTreeSet::from([2]));
+        let ids = playlist.list.iter().map(|entry| entry.track.id_track).collect::<Vec<u64>>();
+        assert_eq!(ids, vec![2, 1]);
+
+        // retagged entries move to their new filters, tracks that are not listed change nothing
+        assert!(playlist.update_track(TrackFile{year: Some(1999), ..TrackFile::test(1)}));
+        assert_eq!(playlist.states().get_playback_state_for_filter(LibraryFilterEntry::Year{year: Some(1999)}), PlaylistState::Queued);
+        assert!(!playlist.update_track(TrackFile::test(9)));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::functions::functions_data_file::read_data_file;
+use crate::functions::functions_data_file::write_data_file;
+use crate::globals::playback_state::GlobalPlayback;
+use crate::globals::playback_state::PlaybackState;
+use crate::state::state_library::LibraryColumn;
+use crate::state::state_library::LibrarySelectMode;
+use crate::state::state_library::LibraryTab;
+use crate::state::state_library::StateLibrary;
+use crate::state::state_playlist::PlaylistRepeat;
+use crate::state::state_playlist::StatePlaylist;
+use crate::types::types_library_entry::TrackFile;
+use color_eyre::Result;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+const SESSION_FILE: &str = "session.toml";
+
+/// Queue, playback position and library view from the previous run
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct SessionFile {
+    /// queue by file path, track ids are only known once the scan has found the files
+    playlist: Vec<PathBuf>,
+    selected: usize,
+    repeat  : PlaylistRepeat,
+    shuffle : bool,
+    #[serde(default)]
+    radio   : bool,
+    elapsed_ms: u64,
+    paused    : bool,
+    library_tab        : LibraryTab,
+    library_column     : LibraryColumn,
+    library_select_mode: LibrarySelectMode,
+    library_filter     : Option<String>,
+    library_track      : Option<PathBuf>,
+}
+
+/// Where playback continues after a restore
+pub struct SessionPlayback {
+    pub start_at: Duration,
+    pub paused  : bool,
+}
+
+impl SessionFile {
+    pub fn save(library: &StateLibrary, playlist: &StatePlaylist) -> Result<()> {
+        let path = |track: &TrackFile| library.paths.get(&track.id_track).map(|path| path.to_path_buf());
+        let (elapsed, paused) = match GlobalPlayback::state() {
+            PlaybackState::Playing => (GlobalPlayback::elapsed(), false),
+            PlaybackState::Paused  => (GlobalPlayback::elapsed(), true),
+            PlaybackState::Loading |
+            PlaybackState::Stopped => (Duration::ZERO, false),
+        };
+        let session = SessionFile{
+            playlist: playlist.list.iter().filter_map(|entry| path(&entry.track)).collect(),
+            // tracks without a path are left out, which moves the current index down
+            selected: playlist.list.iter().take(playlist.selected).filter(|entry| path(&entry.track).is_some()).count(),
+            repeat  : playlist.repeat,
+            shuffle : playlist.shuffle,
+            radio   : playlist.radio,
+            elapsed_ms: elapsed.as_millis() as u64,
+            paused,
+            library_tab        : library.selected_tab,
+            library_column     : library.selected_column,
+            library_select_mode: library.select_mode,
+            library_filter     : library.list_filter.selected_entry().map(|entry| entry.name().to_string()),
+            library_track      : library.list_tracks.selected_entry().and_then(path),
+        };
+        write_data_file(SESSION_FILE, &session)
+    }
+
+    pub fn load() -> Result<Option<SessionFile>> {
+        read_data_file(SESSION_FILE)
+    }
+
+    pub fn restore_library(&self, library: &mut StateLibrary) {
+        library.restore_view(
+            self.library_tab,
+            self.library_column,
+            self.library_select_mode,
+            self.library_filter.as_deref(),
+            self.library_track.as_deref().map(TrackFile::id_for_path),
+        );
+    }
+
+    /// Restores the queue, files that are gone are skipped. None when there is no current track.
+    pub fn restore_playlist(&self, library: &StateLibrary, playlist: &mut StatePlaylist) -> Option<SessionPlayback> {
+        let by_id = library.tracks.iter()
+            .map(|track| (track.id_track, *track))
+            .collect::<BTreeMap<u64, TrackFile>>();
+        let tracks = self.playlist.iter()
+            .map(|path| by_id.get(&TrackFile::id_for_path(path)).copied())
+            .collect::<Vec<Option<TrackFile>>>();
+        let is_current_found = tracks.get(self.selected).is_some_and(|track| track.is_some());
+        let selected = tracks.iter().take(self.selected).flatten().count();
+
+        playlist.shuffle = false;
+        playlist.repeat  = self.repeat;
+        playlist.replace(tracks.into_iter().flatten().collect(), selected);
+        if self.shuffle {
+            playlist.toggle_shuffle();
+        }
+        if self.radio != playlist.radio {
+            playlist.toggle_radio();
+        }
+
+        playlist.get_current_track()?;
+        Some(SessionPlayback{
+            start_at: match is_current_found {
+                true  => Duration::from_millis(self.elapsed_ms),
+                false => Duration::ZERO,
+            },
+            paused: self.paused,
+        })
+    }
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrayvec::ArrayString;
+    use pretty_assertions::assert_eq;
+
+    fn track(number: u8, path: &str) -> TrackFile {
+        TrackFile{
+            track_title : ArrayString::from(path).unwrap(),
+            track_number: Some(number),
+            ..TrackFile::test(TrackFile::id_for_path(path.as_ref()))
+        }
+    }
+
+    #[test]
+    fn test_session_restore_skips_missing_tracks() {
+        let session = SessionFile{
+            playlist: ["/a.flac", "/gone.flac", "/b.flac", "/c.flac"].map(PathBuf::from).to_vec(),
+            selected: 2,
+            repeat  : PlaylistRepeat::All,
+            shuffle : false,
+            radio   : false,
+            elapsed_ms: 1500,
+            paused    : true,
+            library_tab        : LibraryTab::Year,
+            library_column     : LibraryColumn::Tracks,
+            library_select_mode: LibrarySelectMode::Album,
+            library_filter     : None,
+            library_track      : None,
+        };
+        let session: SessionFile = toml::from_str(&toml::to_string(&session).unwrap()).unwrap();
+
+        let mut library = StateLibrary::init();
+        (1..).zip(["/a.flac", "/b.flac", "/c.flac"]).for_each(|(number, path)| library.new_track(track(number, path)));
+        let mut playlist = StatePlaylist::init();
+        let playback = session.restore_playlist(&library, &mut playlist).unwrap();
+
+        assert_eq!(playlist.list.len(), 3);
+        assert_eq!(playlist.get_current_track().map(|track| track.track_title.to_string()), Some("/b.flac".to_string()));
+        assert_eq!(playlist.repeat, PlaylistRepeat::All);
+        assert_eq!(playback.start_at, Duration::from_millis(1500));
+        assert!(playback.paused);
+    }
+}
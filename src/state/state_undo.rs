@@ -0,0 +1,118 @@
+use crate::globals::playback_state::GlobalPlayback;
+use crate::globals::playback_state::PlaybackState;
+use crate::state::state_playlist::PlaylistQueue;
+use crate::state::state_playlist::StatePlaylist;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+/// number of queue edits that can be undone
+const UNDO_LIMIT: usize = 50;
+
+/// Queue edits that can be undone and redone
+#[derive(Debug)]
+pub struct StateUndo {
+    undo: VecDeque<PlaylistSnapshot>,
+    redo: Vec<PlaylistSnapshot>,
+}
+
+/// Queue with the playback position at the time
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct PlaylistSnapshot {
+    pub queue  : PlaylistQueue,
+    pub elapsed: Duration,
+    pub state  : PlaybackState,
+}
+
+impl PlaylistSnapshot {
+    pub fn take(playlist: &StatePlaylist) -> PlaylistSnapshot {
+        PlaylistSnapshot{
+            queue  : playlist.queue(),
+            elapsed: GlobalPlayback::elapsed(),
+            state  : GlobalPlayback::state(),
+        }
+    }
+}
+
+impl StateUndo {
+    pub fn init() -> StateUndo {
+        StateUndo{
+            undo: VecDeque::new(),
+            redo: vec![],
+        }
+    }
+
+    /// Keeps `before` if the queue has changed since, a new edit drops what could be redone
+    pub fn record(&mut self, before: PlaylistSnapshot, after: &StatePlaylist) {
+        if before.queue.list == after.list {
+            return;
+        }
+        if self.undo.len() == UNDO_LIMIT {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(before);
+        self.redo.clear();
+    }
+
+    /// Snapshot to go back to, `current` is kept for redo
+    pub fn undo(&mut self, current: PlaylistSnapshot) -> Option<PlaylistSnapshot> {
+        let snapshot = self.undo.pop_back()?;
+        self.redo.push(current);
+        Some(snapshot)
+    }
+
+    /// Snapshot to go forward to, `current` is kept for undo
+    pub fn redo(&mut self, current: PlaylistSnapshot) -> Option<PlaylistSnapshot> {
+        let snapshot = self.redo.pop()?;
+        self.undo.push_back(current);
+        Some(snapshot)
+    }
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::types_library_entry::TrackFile;
+    use pretty_assertions::assert_eq;
+
+    fn playlist(ids: &[u64]) -> StatePlaylist {
+        let mut playlist = StatePlaylist::init();
+        playlist.replace(ids.iter().copied().map(TrackFile::test).collect(), 0);
+        playlist
+    }
+
+    fn snapshot(ids: &[u64]) -> PlaylistSnapshot {
+        PlaylistSnapshot{queue: playlist(ids).queue(), elapsed: Duration::ZERO, state: PlaybackState::Stopped}
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let ids = |snapshot: Option<PlaylistSnapshot>| snapshot.map(|snapshot| snapshot.queue.list.iter().map(|entry| entry.track.id_track).collect::<Vec<u64>>());
+        let mut undo = StateUndo::init();
+
+        // unchanged queue is not recorded
+        undo.record(snapshot(&[1]), &playlist(&[1]));
+        assert_eq!(ids(undo.undo(snapshot(&[1]))), None);
+
+        undo.record(snapshot(&[1]), &playlist(&[1, 2]));
+        undo.record(snapshot(&[1, 2]), &playlist(&[3]));
+        assert_eq!(ids(undo.undo(snapshot(&[3]))), Some(vec![1, 2]));
+        assert_eq!(ids(undo.redo(snapshot(&[1, 2]))), Some(vec![3]));
+        assert_eq!(ids(undo.undo(snapshot(&[3]))), Some(vec![1, 2]));
+
+        // a new edit drops the redo
+        undo.record(snapshot(&[1, 2]), &playlist(&[4]));
+        assert_eq!(ids(undo.redo(snapshot(&[4]))), None);
+
+        for _ in 0..UNDO_LIMIT+10 {
+            undo.record(snapshot(&[1]), &playlist(&[2]));
+        }
+        assert_eq!(undo.undo.len(), UNDO_LIMIT);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::enums::enum_input::InputCommand;
 use crate::enums::enum_input::InputGlobal;
 use crate::enums::enum_input::InputLocal;
 use crate::globals::terminal_state::GlobalUiState;
@@ -23,11 +24,20 @@ pub fn start_input_listener(tx: MsgChannels) {
 fn input_loop(tx: &MsgChannels) -> Result<()> {
     let send_l = |input: InputLocal | tx.state.send((Instant::now(), StateActions::InputLocal(input)));
     let send_g = |input: InputGlobal| tx.state.send((Instant::now(), StateActions::InputGlobal(input)));
+    let send_c = |input: InputCommand| tx.state.send((Instant::now(), StateActions::InputCommand(input)));
 
+    let mut is_command = false;
     loop {
         if let Ok(event) = event::read() {
             trace!("{:?}", &event);
             match event {
+                Event::Key(key) if is_command => match key.code {
+                    KeyCode::Char(c)   => send_c(InputCommand::Char(c))?,
+                    KeyCode::Backspace => send_c(InputCommand::Backspace)?,
+                    KeyCode::Enter     => {is_command = false; send_c(InputCommand::Submit)?},
+                    KeyCode::Esc       => {is_command = false; send_c(InputCommand::Cancel)?},
+                    _ => (),
+                },
                 Event::Mouse(event) => match event.kind {
                     MouseEventKind::ScrollUp   => send_l(InputLocal::Up)?,
                     MouseEventKind::ScrollDown => send_l(InputLocal::Down)?,
@@ -36,7 +46,9 @@ fn input_loop(tx: &MsgChannels) -> Result<()> {
                 Event::Key(key) => match key.code {
                     // wasd
                     KeyCode::Char('w') => send_l(InputLocal::Up)?,
+                    KeyCode::Char('W') => send_l(InputLocal::MoveUp)?,
                     KeyCode::Char('a') => send_l(InputLocal::Down)?,
+                    KeyCode::Char('A') => send_l(InputLocal::MoveDown)?,
                     KeyCode::Char('s') => send_l(InputLocal::Left)?,
                     KeyCode::Char('d') => send_l(InputLocal::Right)?,
 
@@ -51,9 +63,12 @@ fn input_loop(tx: &MsgChannels) -> Result<()> {
                     KeyCode::Char('G') => send_g(InputGlobal::SkipForward{sec: 60})?,
 
                     KeyCode::Char('e') => send_l(InputLocal::Select)?,
+                    KeyCode::Char('E') => send_l(InputLocal::SelectNext)?,
                     // vim
                     KeyCode::Char('k') => send_l(InputLocal::Up)?,
+                    KeyCode::Char('K') => send_l(InputLocal::MoveUp)?,
                     KeyCode::Char('j') => send_l(InputLocal::Down)?,
+                    KeyCode::Char('J') => send_l(InputLocal::MoveDown)?,
                     KeyCode::Char('h') => send_l(InputLocal::Left)?,
                     KeyCode::Char('l') => send_l(InputLocal::Right)?,
 
@@ -75,6 +90,24 @@ fn input_loop(tx: &MsgChannels) -> Result<()> {
                     KeyCode::Right     => send_l(InputLocal::Right)?,
 
                     KeyCode::Char(' ') => send_l(InputLocal::SelectAlt)?,
+                    KeyCode::Char('p') => send_l(InputLocal::SelectNext)?,
+
+                    KeyCode::Char('+') |
+                    KeyCode::Char('=') => send_g(InputGlobal::VolumeUp)?,
+                    KeyCode::Char('-') => send_g(InputGlobal::VolumeDown)?,
+                    KeyCode::Char('0') => send_g(InputGlobal::Mute)?,
+                    KeyCode::Char('r') => send_g(InputGlobal::Repeat)?,
+                    KeyCode::Char('t') => send_g(InputGlobal::Shuffle)?,
+                    KeyCode::Char('R') => send_g(InputGlobal::Radio)?,
+                    KeyCode::F(5)      => send_g(InputGlobal::Rescan)?,
+                    KeyCode::Char('1') => send_g(InputGlobal::ViewLibrary)?,
+                    KeyCode::Char('2') => send_g(InputGlobal::ViewPlaylist)?,
+                    KeyCode::Char('z') => send_g(InputGlobal::Undo)?,
+                    KeyCode::Char('Z') => send_g(InputGlobal::Redo)?,
+
+                    KeyCode::Delete    |
+                    KeyCode::Backspace => send_l(InputLocal::Remove)?,
+                    KeyCode::Char('X') => send_l(InputLocal::ClearPlayed)?,
 
                     KeyCode::PageUp    => send_l(InputLocal::PgUp)?,
                     KeyCode::PageDown  => send_l(InputLocal::PgDown)?,
@@ -83,6 +116,8 @@ fn input_loop(tx: &MsgChannels) -> Result<()> {
 
                     KeyCode::Tab       => send_l(InputLocal::Tab)?,
                     KeyCode::BackTab   => send_l(InputLocal::RevTab)?,
+
+                    KeyCode::Char(':') => {is_command = true; send_c(InputCommand::Open)?},
                     // Exit
                     KeyCode::Esc |
                     KeyCode::Char('q') => tx.exit.send(Ok("".to_string()))?,
@@ -1,24 +1,38 @@
+use crate::config::conf_playback::ConfPlayback;
+use crate::config::conf_playback::ConfReplayGain;
+use crate::functions::functions_data_file::read_data_file;
+use crate::functions::functions_data_file::write_data_file;
 use crate::globals::playback_state::GlobalPlayback;
 use crate::globals::playback_state::PlaybackState;
+use crate::sounds::sound_queue::QueueEntry;
+use crate::sounds::sound_queue::QueueEvent;
+use crate::sounds::sound_queue::QueueSound;
+use crate::sounds::sound_stream::StreamSound;
 use crate::spawn_thread;
 use crate::tasks::listener_state::StateActions;
 use crate::tasks::listener_updater::UpdateActions;
 use crate::types::types_library_entry::TrackFile;
 use crate::types::types_msg_channels::MsgChannels;
+use crate::CONFIG;
 use awedio::backends::CpalBackend;
 use awedio::manager::Manager;
-use awedio::sounds::open_file;
-use awedio::sounds::wrappers::CompletionNotifier;
 use awedio::sounds::wrappers::Controller;
-use awedio::sounds::MemorySound;
 use awedio::Sound;
+use color_eyre::eyre::eyre;
 use color_eyre::eyre::Context;
+use color_eyre::eyre::Report;
 use color_eyre::Result;
-use color_eyre::Section;
+use crossbeam_channel::unbounded;
 use crossbeam_channel::Receiver;
+use crossbeam_channel::Sender;
+use serde::Deserialize;
+use serde::Serialize;
 use std::collections::BTreeMap;
 use std::collections::VecDeque;
 use std::path::Path;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 use std::time::Instant;
@@ -26,13 +40,21 @@ use std::time::Instant;
 //-//////////////////////////////////////////////////////////////////
 pub enum PlaybackActions {
     NewTrack{track_id: u64, path: Box<Path>},
-    Play{track: Box<TrackFile>, start_at: Option<Duration>},
-    Que{track: Box<TrackFile>},
+    /// `in_album` selects album gain for ReplayGain in auto mode
+    Play{track: Box<TrackFile>, in_album: bool, start_at: Option<Duration>},
+    Que{track: Box<TrackFile>, in_album: bool},
     Replay,
     Pause,
-    Resume(Duration),
+    Resume,
+    /// Keeps the paused or playing state
+    Seek(Duration),
     Next,
-    Callback,
+    /// Drops everything queued after the current track
+    ClearUpcoming,
+    Volume{volume: u8, muted: bool},
+    Callback(QueueEvent),
+    /// Track opened by the loader thread
+    Loaded{key: u64, sound: Result<StreamSound>},
     Clear,
 }
 
@@ -54,7 +76,7 @@ pub fn playback_loop(rx: Receiver<PlaybackActions>, tx: &MsgChannels) -> Result<
                 PlaybackActions::NewTrack{track_id, path} => {
                     tracks.insert(track_id, path);
                 },
-                PlaybackActions::Play { track, start_at } => {
+                PlaybackActions::Play { track, in_album, start_at } => {
                     let path = match tracks.get(&track.id_track) {
                         Some(path) => path,
                         None => {
@@ -69,14 +91,10 @@ pub fn playback_loop(rx: Receiver<PlaybackActions>, tx: &MsgChannels) -> Result<
                             continue;
                         },
                     };
-                    if let Err(err) = state.que(*track, path) {
-                        tx.state.send((Instant::now(), StateActions::PlaybackNextTrack{error: Some(err)}))?;
-                        continue;
-                    }
-                    state.start(start_at)?;
-                    debug_assert!(GlobalPlayback::state() == PlaybackState::Playing);
+                    state.que(*track, in_album, path);
+                    state.start(start_at);
                 },
-                PlaybackActions::Que { track } => {
+                PlaybackActions::Que { track, in_album } => {
                     let path = match tracks.get(&track.id_track) {
                         Some(path) => path,
                         None => {
@@ -91,141 +109,414 @@ pub fn playback_loop(rx: Receiver<PlaybackActions>, tx: &MsgChannels) -> Result<
                             continue;
                         },
                     };
-                    if let Err(err) = state.que(*track, path) {
-                        tx.state.send((Instant::now(), StateActions::PlaybackNextTrack{error: Some(err)}))?;
+                    state.que(*track, in_album, path);
+                },
+                PlaybackActions::Loaded{key, sound} => {
+                    state.loaded(key, sound)?;
+                },
+                PlaybackActions::Callback(QueueEvent::Ended{key, elapsed}) => {
+                    if state.ended(key, elapsed) {
+                        tx.state.send((Instant::now(), StateActions::PlaybackNextTrack{error: None}))?;
                     }
                 },
-                PlaybackActions::Callback => {
-                    state.next()?;
-                    tx.state.send((Instant::now(), StateActions::PlaybackNextTrack{error: None}))?;
+                PlaybackActions::Callback(QueueEvent::Failed{key, error}) => {
+                    state.broken(key, eyre!(error).wrap_err("Decoding track during playback"))?;
                 },
                 PlaybackActions::Replay => {
-                    state.start(None)?;
-                    debug_assert!(GlobalPlayback::state() == PlaybackState::Playing);
+                    state.seek(Duration::ZERO);
+                    state.resume();
                 },
                 PlaybackActions::Pause => {
                     state.pause();
-                    debug_assert!(GlobalPlayback::state() == PlaybackState::Paused);
+                    debug_assert!(matches!(GlobalPlayback::state(), PlaybackState::Paused | PlaybackState::Loading | PlaybackState::Stopped));
                 },
-                PlaybackActions::Resume(play_at) => {
-                    state.resume(play_at)?;
-                    debug_assert!(GlobalPlayback::state() == PlaybackState::Playing);
+                PlaybackActions::Resume => {
+                    state.resume();
+                },
+                PlaybackActions::Seek(position) => {
+                    if state.seek(position) {
+                        tx.state.send((Instant::now(), StateActions::PlaybackNextTrack { error: None }))?;
+                    }
                 },
                 PlaybackActions::Next => {
-                    state.next()?;
+                    state.next();
                     tx.state.send((Instant::now(), StateActions::PlaybackNextTrack { error: None }))?;
                 },
+                PlaybackActions::ClearUpcoming => {
+                    state.clear_upcoming();
+                },
+                PlaybackActions::Volume{volume, muted} => {
+                    state.set_volume(volume, muted);
+                    tx.state.send((Instant::now(), StateActions::Update()))?;
+                },
                 PlaybackActions::Clear => {
                     state.clear();
-                    debug_assert!(GlobalPlayback::state() == PlaybackState::Stopped);
+                    // the loader may still be opening a track that is dropped once it arrives
+                    debug_assert!(matches!(GlobalPlayback::state(), PlaybackState::Loading | PlaybackState::Stopped));
                 },
             },
         }
     }
 }
 
+const VOLUME_FILE: &str = "volume.toml";
+
+#[derive(Serialize, Deserialize)]
+struct VolumeFile {
+    volume: u8,
+}
+
 struct PlaybackManager {
     channels  : MsgChannels,
-    playback  : Option<Playback>,
-    que       : VecDeque<(TrackFile, MemorySound)>,
+    config    : ConfPlayback,
+    output    : Output,
+    loader    : Loader,
+    que       : VecDeque<QueuedTrack>,
+    next_key  : u64,
+    is_started: bool,
+    /// front of the queue is not handed to the output yet, it starts at this position once loaded
+    start_at  : Option<Duration>,
+    /// paused before the pending start, it starts paused once loaded
+    start_paused: bool,
 }
-struct Playback {
+/// Kept open for the whole session, tracks are fed through the queue sound
+struct Output {
     pub manager   : Manager,
     pub backend   : CpalBackend,
-    pub controller: Controller<CompletionNotifier<MemorySound>>,
+    pub controller: Controller<QueueSound>,
+}
+/// Opens tracks in the background so the playback thread keeps answering control messages
+struct Loader {
+    pub tx_requests : Sender<LoadRequest>,
+    /// requests for keys below this are skipped, raised when the queue is cleared
+    pub cancel_below: Arc<AtomicU64>,
+}
+struct LoadRequest {
+    pub key     : u64,
+    pub track_id: u64,
+    pub path    : Box<Path>,
+}
+struct QueuedTrack {
+    pub key  : u64,
+    pub track: TrackFile,
+    pub gain : f32,
+    pub path : Box<Path>,
+    pub sound: QueuedSound,
+}
+enum QueuedSound {
+    Loading,
+    /// opened ahead of time, handed to the output when playback reaches it
+    Ready(StreamSound),
+    Output,
 }
 
 impl PlaybackManager {
     pub fn new(channels: MsgChannels) -> Result<PlaybackManager> {
-        Ok(PlaybackManager {
+        let config = CONFIG.get().unwrap().playback;
+        let crossfade = match config.crossfade {
+            false => None,
+            true  => Some((Duration::from_secs_f32(config.crossfade_sec.max(0.0)), config.crossfade_curve)),
+        };
+
+        let (tx_events, rx_events) = unbounded();
+        let (sound, controller) = QueueSound::new(tx_events, crossfade).controllable();
+
+        // Note that output is always sent to alsa as long as manager and backend lives.
+        // Witch causes CPU usage and makes app show up in mixer.
+        let (mut manager, backend) = awedio::start().context("Starting audio output")?;
+        manager.play(Box::new(sound));
+
+        spawn_thread!(channels.clone(), "play-callback", move |tx: MsgChannels| {
+            for event in rx_events.iter() {
+                if tx.playback.send(PlaybackActions::Callback(event)).is_err() {
+                    break;
+                }
+            }
+            info!("No longer listening for end of track. Output has been closed.");
+        })?;
+
+        let (tx_requests, rx_requests) = unbounded::<LoadRequest>();
+        let cancel_below = Arc::new(AtomicU64::new(0));
+        let loader_cancel_below = cancel_below.clone();
+        spawn_thread!(channels.clone(), "play-loader", move |tx: MsgChannels| {
+            for LoadRequest{key, track_id, path} in rx_requests.iter() {
+                if key < loader_cancel_below.load(Ordering::Relaxed) {
+                    continue;
+                }
+                info!("loading track {:?}", path);
+                GlobalPlayback::set_loading(Some(track_id), &tx);
+                let sound = StreamSound::open(&path);
+                GlobalPlayback::set_loading(None, &tx);
+                if tx.playback.send(PlaybackActions::Loaded{key, sound}).is_err() {
+                    break;
+                }
+            }
+        })?;
+
+        let mut state = PlaybackManager {
             channels,
-            playback: None,
+            config,
+            output: Output{
+                manager,
+                backend,
+                controller,
+            },
+            loader: Loader{
+                tx_requests,
+                cancel_below,
+            },
             que: VecDeque::new(),
-        })
+            next_key: 0,
+            is_started: false,
+            start_at: None,
+            start_paused: false,
+        };
+
+        match read_data_file::<VolumeFile>(VOLUME_FILE) {
+            Ok(Some(file)) => state.set_volume(file.volume, false),
+            Ok(None)       => {},
+            Err(err)       => error!("Restoring volume: {:?}", err),
+        }
+
+        Ok(state)
+    }
+
+    /// `volume` in percent, persisted across restarts
+    pub fn set_volume(&mut self, volume: u8, muted: bool) {
+        let volume = volume.min(100);
+        let gain = match muted {
+            true  => 0.0,
+            false => (volume as f32 / 100.0).powi(2), // closer to perceived loudness than linear steps
+        };
+        self.output.controller.send_command(Box::new(move |queue: &mut QueueSound| queue.set_gain(gain)));
+
+        if volume != GlobalPlayback::volume() {
+            if let Err(err) = write_data_file(VOLUME_FILE, &VolumeFile{volume}) {
+                error!("Saving volume: {:?}", err);
+            }
+        }
+        GlobalPlayback::set_volume(volume, muted);
     }
 
     pub fn stop(&mut self) {
-        self.playback = None;
+        self.is_started   = false;
+        self.start_at     = None;
+        self.start_paused = false;
+        self.output.controller.send_command(Box::new(|queue: &mut QueueSound| queue.clear()));
     }
 
     pub fn clear(&mut self) {
         self.stop();
         self.que.clear();
+        self.loader.cancel_below.store(self.next_key, Ordering::Relaxed);
         GlobalPlayback::stop_playback();
+        self.channels.update.send(UpdateActions::Playback(false)).unwrap();
+    }
+
+    pub fn clear_upcoming(&mut self) {
+        if self.que.len() < 2 {
+            return;
+        }
+        for QueuedTrack{key, sound, ..} in self.que.drain(1..) {
+            if let QueuedSound::Output = sound {
+                self.output.controller.send_command(Box::new(move |queue: &mut QueueSound| queue.remove(key)));
+            }
+        }
+    }
+
+    /// ReplayGain multiplier for `track` according to the configured mode
+    fn replay_gain(&self, track: &TrackFile, in_album: bool) -> f32 {
+        let preamp = self.config.replay_gain_preamp_db;
+        match self.config.replay_gain {
+            ConfReplayGain::Off   => 1.0,
+            ConfReplayGain::Track => track.replay_gain.multiplier(false, preamp),
+            ConfReplayGain::Album => track.replay_gain.multiplier(true, preamp),
+            ConfReplayGain::Auto  => track.replay_gain.multiplier(in_album, preamp),
+        }
+    }
+
+    fn request_load(&self, key: u64, track: &TrackFile, path: &Path) {
+        let request = LoadRequest{key, track_id: track.id_track, path: path.into()};
+        self.loader.tx_requests.send(request).unwrap();
     }
 
-    pub fn que(&mut self, track: TrackFile, path: &Path) -> Result<()> {
+    pub fn que(&mut self, track: TrackFile, in_album: bool, path: &Path) {
         info!("queuing track {:?}", path);
-        GlobalPlayback::set_loading(Some(track.id_track), &self.channels);
-        let sound = open_file(path)?.into_memory_sound()?;
-        GlobalPlayback::set_loading(None, &self.channels);
-        self.que.push_back((track, sound));
+        let key  = self.next_key;
+        let gain = self.replay_gain(&track, in_album);
+        self.next_key += 1;
+
+        self.request_load(key, &track, path);
+        self.que.push_back(QueuedTrack{
+            key,
+            track,
+            gain,
+            path: path.into(),
+            sound: QueuedSound::Loading,
+        });
+    }
+
+    /// Result from the loader thread
+    pub fn loaded(&mut self, key: u64, sound: Result<StreamSound>) -> Result<()> {
+        let index = match self.que.iter().position(|queued| queued.key == key) {
+            Some(index) if matches!(self.que[index].sound, QueuedSound::Loading) => index,
+            _ => return Ok(()), // removed or loaded again in the meantime
+        };
+        match sound {
+            Ok(sound) => {
+                self.que[index].sound = QueuedSound::Ready(sound);
+                self.flush();
+                Ok(())
+            },
+            Err(err) => self.broken(key, err.wrap_err("Opening track")),
+        }
+    }
+
+    /// Drops a track that can not be played and reports it to state, which decides what plays instead.
+    /// Playback is stopped if it was the current track.
+    pub fn broken(&mut self, key: u64, error: Report) -> Result<()> {
+        let index = match self.que.iter().position(|queued| queued.key == key) {
+            None        => return Ok(()),
+            Some(index) => index,
+        };
+        let track_id = self.que[index].track.id_track;
+        match index {
+            0 => self.clear(),
+            _ => {
+                self.que.remove(index);
+                self.output.controller.send_command(Box::new(move |queue: &mut QueueSound| queue.remove(key)));
+            },
+        }
+        self.channels.state.send((Instant::now(), StateActions::TrackBroken{track_id, error}))?;
         Ok(())
     }
 
-    pub fn start(
-        &mut self,
-        start_at: Option<Duration>,
-    ) -> Result<()> {
+    /// Hands loaded tracks to the running output in queue order
+    fn flush(&mut self) {
+        if !self.is_started {
+            return;
+        }
+        for (index, queued) in self.que.iter_mut().enumerate() {
+            let mut sound = match std::mem::replace(&mut queued.sound, QueuedSound::Output) {
+                QueuedSound::Output       => continue,
+                QueuedSound::Ready(sound) => sound,
+                QueuedSound::Loading      => {
+                    queued.sound = QueuedSound::Loading;
+                    break;
+                },
+            };
+            if let (0, Some(start_at)) = (index, self.start_at.take()) {
+                sound.seek(start_at);
+                GlobalPlayback::start_playback(queued.track.id_track, queued.key, start_at, queued.track.duration);
+                if self.start_paused {
+                    GlobalPlayback::pause_playback();
+                }
+                self.channels.update.send(UpdateActions::Playback(!self.start_paused)).unwrap();
+            }
+            let entry = QueueEntry::new(queued.key, &queued.track, queued.gain, sound);
+            self.output.controller.send_command(Box::new(move |queue: &mut QueueSound| queue.push(entry)));
+        }
+    }
+
+    /// Playback begins as soon as the first track is loaded
+    pub fn start(&mut self, start_at: Option<Duration>) {
         if self.que.is_empty() {
             self.clear();
-            return Ok(());
+            return;
         }
         self.stop();
-        if let Some((track, sound)) = self.que.front() {
-            let (    sound, notifier  ) = sound.clone().with_completion_notifier();
-            let (mut sound, controller) = sound.controllable();
 
-            if let Some(duration) = start_at {
-                let _ = sound.skip(duration);
+        // tracks already given to the output have been consumed and are opened again
+        for index in 0..self.que.len() {
+            if matches!(self.que[index].sound, QueuedSound::Output) {
+                let QueuedTrack{key, track, path, ..} = &self.que[index];
+                self.request_load(*key, track, path);
+                self.que[index].sound = QueuedSound::Loading;
             }
-
-            // Note that output is always sent to alsa as long as manager and backend lives.
-            // Witch causes CPU usage and makes app show up in mixer.
-            let (mut manager, backend) = awedio::start().context("Starting audio output")?;
-            manager.play(Box::new(sound));
-            GlobalPlayback::start_playback(track.id_track, start_at.unwrap_or_default(), track.duration);
-
-            let track_id = track.id_track;
-            spawn_thread!(self.channels.clone(), "play-callback", move |tx: MsgChannels| {
-                let res = notifier.recv()
-                    .context("Awaiting track playback completed callback")
-                    .note("This error is expected when playback is intentionally stopped.");
-                match res {
-                    Err(err) => match GlobalPlayback::state() == PlaybackState::Playing && GlobalPlayback::playing() == track_id  {
-                        true  => error!("{:?}", err),
-                        false => info!("No longer listening for end of track. Track has been stopped."),
-                    },
-                    Ok(_) => tx.playback.send(PlaybackActions::Callback).unwrap(),
-                }
-            })?;
-
-            self.playback = Some(Playback{
-                manager,
-                backend,
-                controller
-            });
-            self.channels.update.send(UpdateActions::Playback(true)).unwrap();
         }
-        Ok(())
+        self.is_started = true;
+        self.start_at   = Some(start_at.unwrap_or_default());
+        self.flush();
     }
 
-    pub fn next(&mut self) -> Result<()> {
+    /// Output moved past the track with `key`, returns false if the event is stale
+    pub fn ended(&mut self, key: u64, elapsed: Duration) -> bool {
+        match self.que.front() {
+            Some(QueuedTrack{key: front, ..}) if *front == key => {},
+            _ => return false,
+        }
         self.que.pop_front();
-        self.start(None)?;
-        Ok(())
+        match self.que.front() {
+            None => self.clear(),
+            Some(QueuedTrack{key, track, ..}) => GlobalPlayback::start_playback(track.id_track, *key, elapsed, track.duration),
+        }
+        true
+    }
+
+    pub fn next(&mut self) {
+        let key = match self.que.front() {
+            None => return,
+            Some(QueuedTrack{key, ..}) => *key,
+        };
+        match self.is_started && self.start_at.is_none() {
+            false => {
+                self.que.pop_front();
+                self.start(None);
+            },
+            true => {
+                self.output.controller.send_command(Box::new(move |queue: &mut QueueSound| queue.remove(key)));
+                self.ended(key, Duration::ZERO);
+            },
+        }
     }
 
     pub fn pause(&mut self) {
+        if !self.is_started {
+            return;
+        }
+        self.output.controller.send_command(Box::new(|queue: &mut QueueSound| queue.set_paused(true)));
+        if self.start_at.is_some() {
+            self.start_paused = true;
+            return;
+        }
         GlobalPlayback::pause_playback();
-        self.stop();
         self.channels.update.send(UpdateActions::Playback(false)).unwrap();
     }
 
-    pub fn resume(&mut self, resume_at: Duration) -> Result<()> {
-        self.start(Some(resume_at))?;
+    pub fn resume(&mut self) {
+        if !self.is_started {
+            return self.start(None);
+        }
+        self.output.controller.send_command(Box::new(|queue: &mut QueueSound| queue.set_paused(false)));
+        if self.start_at.is_some() {
+            self.start_paused = false;
+            return;
+        }
+        GlobalPlayback::resume_playback();
         self.channels.update.send(UpdateActions::Playback(true)).unwrap();
-        Ok(())
+    }
+
+    /// Seeks within the current track, seeking past its end moves on to the next track.
+    /// Without a tagged duration the stream ends on its own instead.
+    /// Returns true when the current track was skipped.
+    pub fn seek(&mut self, position: Duration) -> bool {
+        let (key, duration) = match self.que.front() {
+            None => return false,
+            Some(QueuedTrack{key, track, ..}) => (*key, track.duration),
+        };
+        if !duration.is_zero() && position >= duration {
+            self.next();
+            return true;
+        }
+        match (self.is_started, self.start_at.is_some()) {
+            (false, _    ) => self.start(Some(position)),
+            (true , true ) => self.start_at = Some(position),
+            (true , false) => {
+                self.output.controller.send_command(Box::new(move |queue: &mut QueueSound| queue.seek(key, position)));
+                GlobalPlayback::seek_playback(position);
+            },
+        }
+        false
     }
 }
 //-//////////////////////////////////////////////////////////////////
@@ -2,15 +2,24 @@ use crate::globals::terminal_state::GlobalUiState;
 use crate::tasks::listener_playback::PlaybackActions;
 use crate::tasks::listener_state::StateActions;
 use crate::tasks::listener_updater::UpdateActions;
+use crate::types::types_library_cache::CachedTrack;
+use crate::types::types_library_cache::LibraryCache;
 use crate::types::types_library_entry::TrackFile;
 use crate::types::types_msg_channels::MsgChannels;
 use crate::CONFIG;
 use color_eyre::eyre::OptionExt;
 use color_eyre::Result;
+use crossbeam_channel::unbounded;
+use crossbeam_channel::Receiver;
+use crossbeam_channel::Sender;
 use rayon::Scope;
+use rayon::ThreadPool;
 use rayon::ThreadPoolBuilder;
+use std::collections::BTreeMap;
 use std::fs;
+use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Instant;
 use std::time::SystemTime;
 
@@ -49,14 +58,27 @@ const EXTENSIONS: [&str; 26] = [
 //-////////////////////////////////////////////////////////////////////////////
 //
 //-////////////////////////////////////////////////////////////////////////////
-pub fn start_fs_scanner_listener(tx: MsgChannels) {
-    if let Err(err) = scanner_loop(&tx) {
+pub enum ScanActions {
+    /// Walks the media dirs again and applies what changed since the last scan
+    Rescan,
+}
+
+/// Shared by every directory of one scan
+#[derive(Clone)]
+struct ScanContext {
+    tx: MsgChannels,
+    cache: Arc<BTreeMap<PathBuf, CachedTrack>>,
+    tx_cache: Sender<CachedTrack>,
+}
+
+pub fn start_fs_scanner_listener(tx: MsgChannels, rx: Receiver<ScanActions>) {
+    if let Err(err) = scanner_loop(&tx, rx) {
         error!("scan error: {:?}", err);
         tx.exit.send(Err(err)).unwrap();
     }
 }
 
-fn scan_directory(scope: &Scope, dir: PathBuf, tx: MsgChannels) {
+fn scan_directory(scope: &Scope, dir: PathBuf, ctx: ScanContext) {
     if let Ok(dir) = fs::read_dir(dir) {
         for entry in dir.into_iter().filter_map(|e| e.ok()) {
             let path = entry.path();
@@ -65,61 +87,150 @@ fn scan_directory(scope: &Scope, dir: PathBuf, tx: MsgChannels) {
                 if path.file_name().unwrap().to_string_lossy().starts_with(".") {
                     continue;
                 }
-                let tx = tx.clone();
-                scope.spawn(move |scope| scan_directory(scope, path, tx));
+                let ctx = ctx.clone();
+                GlobalUiState::add_scan_dir();
+                scope.spawn(move |scope| scan_directory(scope, path, ctx));
                 continue;
             }
             if path.is_file() {
-                let extension = path.extension().unwrap_or_default().to_str().unwrap_or_default();
-                if EXTENSIONS.contains(&extension) {
-                    let tx_state = tx.state.clone();
-                    let tx_playback = tx.playback.clone();
+                if is_track_file(&path) {
+                    GlobalUiState::add_scan_found();
+                    let meta = match fs::metadata(&path) {
+                        Ok(meta) => meta,
+                        Err(e) => {
+                            error!("Reading file metadata error: {:?} {:?}", path, e);
+                            GlobalUiState::add_scan_failed();
+                            continue;
+                        },
+                    };
+                    // the library already has unchanged files from the cache, only changed files are parsed
+                    if let Some(cached) = ctx.cache.get(&path).filter(|cached| cached.is_current(&meta)) {
+                        ctx.tx_cache.send(cached.clone()).unwrap();
+                        GlobalUiState::add_scan_parsed();
+                        continue;
+                    }
+                    let ctx = ctx.clone();
                     scope.spawn(move |_| match TrackFile::new(&path) {
                         Ok(track) => {
                             if track.album_title.map(|s| s.starts_with("Beautiful")).unwrap_or(false) {
                                 info!("{:?}", &track);
                             }
-                            tx_state
-                                .send((Instant::now(), StateActions::ScanAddSong { track: Box::new(track) }))
-                                .unwrap();
-                            tx_playback
-                                .send(PlaybackActions::NewTrack {
-                                    track_id: track.id_track,
-                                    path: path.into_boxed_path(),
-                                })
-                                .unwrap();
+                            ctx.tx_cache.send(CachedTrack::new(&path, &meta, &track)).unwrap();
+                            add_track(&ctx.tx, track, path, false);
+                            GlobalUiState::add_scan_parsed();
+                        },
+                        Err(e) => {
+                            error!("Parse track error: {:?} {:?}", path, e);
+                            GlobalUiState::add_scan_failed();
                         },
-                        Err(e) => error!("Parse track error: {:?} {:?}", path, e),
                     });
                 }
                 continue;
             }
         }
     }
+    GlobalUiState::finish_scan_dir();
+}
+
+/// File extension is one lofty can parse
+pub fn is_track_file(path: &Path) -> bool {
+    let extension = path.extension().unwrap_or_default().to_str().unwrap_or_default();
+    EXTENSIONS.contains(&extension)
+}
+
+/// Cached tracks are new to the library, parsed tracks can replace ones that were cached
+fn add_track(tx: &MsgChannels, track: TrackFile, path: PathBuf, is_cached: bool) {
+    let state = match is_cached {
+        true  => StateActions::ScanAddSong    { track: Box::new(track), path: path.clone().into_boxed_path() },
+        false => StateActions::ScanUpdateSong { track: Box::new(track), path: path.clone().into_boxed_path() },
+    };
+    tx.state
+        .send((Instant::now(), state))
+        .unwrap();
+    tx.playback
+        .send(PlaybackActions::NewTrack {
+            track_id: track.id_track,
+            path: path.into_boxed_path(),
+        })
+        .unwrap();
 }
 
-fn scanner_loop(tx: &MsgChannels) -> Result<()> {
+fn scanner_loop(tx: &MsgChannels, rx: Receiver<ScanActions>) -> Result<()> {
     let dirs = &CONFIG.get().ok_or_eyre("Config not initialized")?.media_dirs;
+    let pool = ThreadPoolBuilder::new().build()?;
 
+    let mut cache = match LibraryCache::load() {
+        Ok(cache) => cache,
+        Err(err) => {
+            warn!("Loading library cache: {:?}", err);
+            BTreeMap::new()
+        },
+    };
+    // the library is filled from the cache right away, the scan then applies what changed on disk
+    info!("{} tracks from cache", cache.len());
+    for cached in cache.values() {
+        add_track(tx, cached.track(), cached.path.clone(), true);
+    }
+    cache = scan(tx, &pool, dirs, cache)?;
+
+    loop {
+        match rx.recv() {
+            Err(_) => break,
+            Ok(ScanActions::Rescan) => cache = scan(tx, &pool, dirs, cache)?,
+        }
+    }
+
+    info!("scan thread exit");
+    Ok(())
+}
+
+/// Scans all media dirs against the tracks the library has from `cache`, returns and saves the cache for the tracks that were found
+fn scan(tx: &MsgChannels, pool: &ThreadPool, dirs: &[PathBuf], cache: BTreeMap<PathBuf, CachedTrack>) -> Result<BTreeMap<PathBuf, CachedTrack>> {
+    GlobalUiState::start_scan();
     GlobalUiState::update_scanning_state(true);
     tx.update.send(UpdateActions::LoadingLibrary(true)).unwrap();
     info!("Starting scan of '{:?}'", dirs);
 
     let time = SystemTime::now();
 
-    ThreadPoolBuilder::new().build().unwrap().scope(|scope: &Scope| {
+    let cache = Arc::new(cache);
+    let (tx_cache, rx_cache) = unbounded();
+    let ctx = ScanContext{tx: tx.clone(), cache: cache.clone(), tx_cache};
+
+    pool.scope(|scope: &Scope| {
         for dir in dirs.iter() {
-            let tx = tx.clone();
-            scope.spawn(|scope| scan_directory(scope, dir.clone(), tx));
+            let ctx = ctx.clone();
+            GlobalUiState::add_scan_dir();
+            scope.spawn(|scope| scan_directory(scope, dir.clone(), ctx));
         }
     });
 
     info!("scan of all directories took: {:?}", SystemTime::now().duration_since(time)?);
+
+    drop(ctx);
+    let tracks = rx_cache.iter()
+        .map(|track| (track.path.clone(), track))
+        .collect::<BTreeMap<PathBuf, CachedTrack>>();
+    let cached = tracks.values().filter(|track| cache.get(&track.path) == Some(track)).count();
+    info!("{} tracks, {} from cache, {} parsed", tracks.len(), cached, tracks.len() - cached);
+
+    // cached files that were not found again, or failed to parse, are gone from the library
+    let removed = cache.keys()
+        .filter(|path| !tracks.contains_key(*path))
+        .map(|path| path.clone().into_boxed_path())
+        .collect::<Vec<Box<Path>>>();
+    if !removed.is_empty() {
+        tx.state.send((Instant::now(), StateActions::ScanRemovePaths{paths: removed}))?;
+    }
+
     GlobalUiState::update_scanning_state(false);
     tx.update.send(UpdateActions::LoadingLibrary(false)).unwrap();
+    tx.state.send((Instant::now(), StateActions::ScanFinished))?;
 
-    info!("scan thread exit");
-    Ok(())
+    if let Err(err) = LibraryCache::save(tracks.values().cloned().collect()) {
+        error!("Saving library cache: {:?}", err);
+    }
+    Ok(tracks)
 }
 //-////////////////////////////////////////////////////////////////////////////
 //
@@ -1,28 +1,73 @@
+use crate::config::expand_home_dir;
+use crate::enums::enum_input::InputCommand;
 use crate::enums::enum_input::InputEffect;
 use crate::enums::enum_input::InputGlobal;
 use crate::enums::enum_input::InputGlobalEffect;
 use crate::enums::enum_input::InputLocal;
+use crate::functions::functions_playlist_file::read_playlist_file;
+use crate::functions::functions_playlist_file::write_playlist_file;
+use crate::functions::functions_radio::pick_radio_tracks;
 use crate::globals::playback_state::GlobalPlayback;
 use crate::globals::playback_state::PlaybackState;
 use crate::state::state_app::AppState;
+use crate::state::state_library::StateLibrary;
+use crate::state::state_interface::CurrentView;
+use crate::state::state_playlist::StatePlaylist;
+use crate::state::state_playlist::PlaylistRepeat;
+use crate::state::state_session::SessionFile;
+use crate::state::state_undo::PlaylistSnapshot;
+use crate::state::state_undo::StateUndo;
 use crate::tasks::listener_playback::PlaybackActions;
+use crate::tasks::listener_scanner::ScanActions;
 use crate::tasks::listener_tui::RenderActions;
+use crate::tasks::listener_updater::UpdateActions;
 use crate::types::types_library_entry::TrackFile;
 use crate::types::types_msg_channels::MsgChannels;
+use crate::CONFIG;
+use arrayvec::ArrayString;
+use color_eyre::eyre::bail;
 use color_eyre::Report;
 use color_eyre::Result;
 use crossbeam_channel::Receiver;
+use crossbeam_channel::Sender;
+use directories::BaseDirs;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
 use std::time::Duration;
 use std::time::Instant;
 
 //-////////////////////////////////////////////////////////////////////////////
 //
 //-////////////////////////////////////////////////////////////////////////////
+/// volume change per key press in percent
+const VOLUME_STEP: u8 = 5;
+/// how long messages stay in the status bar
+const STATUS_DURATION: Duration = Duration::from_secs(5);
+/// tracks added each time radio extends the queue
+const RADIO_BATCH: usize = 3;
+/// recently played tracks the radio picks are related to
+const RADIO_SEEDS: usize = 5;
+/// recently played tracks the radio will not pick again, at most half the library
+const RADIO_AVOID: usize = 200;
+
 pub enum StateActions {
     InputLocal(InputLocal),
     InputGlobal(InputGlobal),
+    InputCommand(InputCommand),
     PlaybackNextTrack{error: Option<Report>},
-    ScanAddSong{track: Box<TrackFile>},
+    /// Track could not be opened or decoded and was dropped from playback
+    TrackBroken{track_id: u64, error: Report},
+    /// Track from the library cache, added before the media dirs are scanned
+    ScanAddSong{track: Box<TrackFile>, path: Box<Path>},
+    /// File was added or changed since it was cached
+    ScanUpdateSong{track: Box<TrackFile>, path: Box<Path>},
+    /// Files or directories are gone, tracks below them are removed
+    ScanRemovePaths{paths: Vec<Box<Path>>},
+    /// All media dirs have been scanned once
+    ScanFinished,
+    /// Saves the session before the app exits, answers on `done`
+    Exit{done: Sender<()>},
     Update(),
     Render(),
 }
@@ -40,31 +85,47 @@ fn state_loop(rx: Receiver<(Instant, StateActions)>, tx: MsgChannels) -> Result<
     let mut state         = AppState::init();
     let mut render_queued = false;
     let mut render_last   = Instant::now();
+    // the session is not saved before it has been restored, that would overwrite it with an empty one
+    let mut is_restored   = false;
+    let mut undo          = StateUndo::init();
 
     loop {
         match rx.recv() {
             Err(err) => return Err(err.into()),
             Ok((render_start, msg)) => {
                 let render_received = render_start.elapsed();
+                // queue before an edit, only taken for inputs that change the queue
+                let mut before_edit = None;
 
                 // Handle input
                 match msg {
                     StateActions::InputLocal(input) => {
-                        state.mutate(|_, library, playlist| {
+                        state.mutate(|interface, library, playlist| {
+                            if let CurrentView::Playlist = interface.current_view {
+                                if is_queue_edit(&input, playlist) {
+                                    before_edit = Some(PlaylistSnapshot::take(playlist));
+                                }
+                                let is_playable = |track: &TrackFile| !library.is_broken(track.id_track);
+                                return playlist_input(&tx, playlist, is_playable, input);
+                            }
                             let effect = library.handle_input(input);
+                            if let InputEffect::Global(_) = effect {
+                                before_edit = Some(PlaylistSnapshot::take(playlist));
+                            }
                             match effect {
                                 InputEffect::Local(effect) => library.handle_input_effect(effect),
                                 InputEffect::Global(effect) => match effect {
-                                    InputGlobalEffect::AppendTracks(tracks) => tracks.into_iter().for_each(|track| playlist.append(track)),
+                                    InputGlobalEffect::AppendTracks(tracks) => playlist.append(tracks),
+                                    InputGlobalEffect::InsertTracksNext(tracks) => {
+                                        playlist.insert_next(tracks);
+                                        requeue_next(&tx, playlist, |track: &TrackFile| !library.is_broken(track.id_track));
+                                    },
                                     InputGlobalEffect::ReplaceTracksAndPlay{tracks, index} => {
+                                        let is_playable = |track: &TrackFile| !library.is_broken(track.id_track);
                                         playlist.replace(tracks, index);
+                                        playlist.skip_unplayable(is_playable);
                                         tx.playback.send(PlaybackActions::Clear).unwrap();
-                                        if let Some(track) = playlist.get_current_track() {
-                                            tx.playback.send(PlaybackActions::Play{track: Box::new(track), start_at: None}).unwrap();
-                                        }
-                                        if let Some(track) = playlist.get_next_track() {
-                                            tx.playback.send(PlaybackActions::Que{track: Box::new(track)}).unwrap();
-                                        }
+                                        play_current(&tx, playlist, is_playable);
                                     },
                                 },
                                 InputEffect::None => {},
@@ -72,75 +133,224 @@ fn state_loop(rx: Receiver<(Instant, StateActions)>, tx: MsgChannels) -> Result<
                         });
                     },
                     StateActions::InputGlobal(input) => {
-                        state.mutate(|_, _, playlist| match input {
+                        state.mutate(|interface, library, playlist| match input {
                             InputGlobal::PlayPause => {
                                 match GlobalPlayback::state() {
                                     PlaybackState::Playing => tx.playback.send(PlaybackActions::Pause).unwrap(),
-                                    PlaybackState::Paused  => tx.playback.send(PlaybackActions::Resume(GlobalPlayback::elapsed())).unwrap(),
+                                    PlaybackState::Paused  => tx.playback.send(PlaybackActions::Resume).unwrap(),
                                     PlaybackState::Loading |
                                     PlaybackState::Stopped => {},
                                 }
                             },
                             InputGlobal::Previous => {
-                                match GlobalPlayback::elapsed() > Duration::from_secs(5) {
+                                match GlobalPlayback::snapshot().elapsed > Duration::from_secs(5) {
                                     true => tx.playback.send(PlaybackActions::Replay).unwrap(),
                                     false => {
-                                        playlist.previous();
+                                        let is_playable = |track: &TrackFile| !library.is_broken(track.id_track);
+                                        playlist.previous(is_playable);
                                         tx.playback.send(PlaybackActions::Clear).unwrap();
-                                        if let Some(track) = playlist.get_current_track() {
-                                            tx.playback.send(PlaybackActions::Play{track: Box::new(track), start_at: None}).unwrap();
-                                        }
-                                        if let Some(track) = playlist.get_next_track() {
-                                            tx.playback.send(PlaybackActions::Que{track: Box::new(track)}).unwrap();
-                                        }
+                                        play_current(&tx, playlist, is_playable);
                                     },
                                 }
                             },
-                            InputGlobal::Next => {
-                                tx.playback.send(PlaybackActions::Next).unwrap();
+                            InputGlobal::Next => match playlist.repeat {
+                                // the preloaded next track is the current one again
+                                PlaylistRepeat::One => {
+                                    let is_playable = |track: &TrackFile| !library.is_broken(track.id_track);
+                                    playlist.skip(is_playable);
+                                    if GlobalPlayback::state() != PlaybackState::Stopped {
+                                        tx.playback.send(PlaybackActions::Clear).unwrap();
+                                        play_current(&tx, playlist, is_playable);
+                                    }
+                                },
+                                _ => tx.playback.send(PlaybackActions::Next).unwrap(),
                             },
                             InputGlobal::Stop => {
+                                before_edit = Some(PlaylistSnapshot::take(playlist));
                                 playlist.clear();
                                 tx.playback.send(PlaybackActions::Clear).unwrap();
                             },
                             InputGlobal::SkipBackward{sec} => {
                                 let dur     = Duration::from_secs(sec as u64);
-                                let elapsed = GlobalPlayback::elapsed();
-                                match elapsed < dur {
-                                    true => {
-                                        tx.playback.send(PlaybackActions::Replay).unwrap();
-                                    },
-                                    false => {
-                                        let new_elapsed = elapsed - dur;
-                                        tx.playback.send(PlaybackActions::Resume(new_elapsed)).unwrap();
-                                    },
-                                }
+                                let elapsed = GlobalPlayback::snapshot().elapsed;
+                                let new_elapsed = elapsed.saturating_sub(dur);
+                                tx.playback.send(PlaybackActions::Seek(new_elapsed)).unwrap();
                             },
                             InputGlobal::SkipForward{sec} => {
                                 let dur     = Duration::from_secs(sec as u64);
-                                let elapsed = GlobalPlayback::elapsed();
+                                let elapsed = GlobalPlayback::snapshot().elapsed;
                                 let new_elapsed = elapsed + dur;
-                                tx.playback.send(PlaybackActions::Resume(new_elapsed)).unwrap();
+                                tx.playback.send(PlaybackActions::Seek(new_elapsed)).unwrap();
+                            },
+                            InputGlobal::VolumeUp => {
+                                let volume = GlobalPlayback::volume().saturating_add(VOLUME_STEP).min(100);
+                                tx.playback.send(PlaybackActions::Volume{volume, muted: false}).unwrap();
+                            },
+                            InputGlobal::VolumeDown => {
+                                let volume = GlobalPlayback::volume().saturating_sub(VOLUME_STEP);
+                                tx.playback.send(PlaybackActions::Volume{volume, muted: false}).unwrap();
+                            },
+                            InputGlobal::Mute => {
+                                let volume = GlobalPlayback::volume();
+                                tx.playback.send(PlaybackActions::Volume{volume, muted: !GlobalPlayback::muted()}).unwrap();
+                            },
+                            InputGlobal::Repeat => {
+                                playlist.cycle_repeat();
+                                requeue_next(&tx, playlist, |track: &TrackFile| !library.is_broken(track.id_track));
+                            },
+                            InputGlobal::Shuffle => {
+                                playlist.toggle_shuffle();
+                                requeue_next(&tx, playlist, |track: &TrackFile| !library.is_broken(track.id_track));
+                            },
+                            InputGlobal::Radio => {
+                                playlist.toggle_radio();
+                                let status = match playlist.radio {
+                                    true  => "Radio on",
+                                    false => "Radio off",
+                                };
+                                interface.set_status(status, STATUS_DURATION);
+                                render_after(&tx, STATUS_DURATION);
+                            },
+                            InputGlobal::Rescan => {
+                                interface.set_status(&rescan(&tx), STATUS_DURATION);
+                                render_after(&tx, STATUS_DURATION);
+                            },
+                            InputGlobal::Undo | InputGlobal::Redo => {
+                                let current  = PlaylistSnapshot::take(playlist);
+                                let snapshot = match input {
+                                    InputGlobal::Undo => undo.undo(current),
+                                    _                 => undo.redo(current),
+                                };
+                                if let Some(snapshot) = snapshot {
+                                    restore_snapshot(&tx, playlist, snapshot, |track: &TrackFile| !library.is_broken(track.id_track));
+                                }
+                            },
+                            InputGlobal::ViewLibrary  => interface.current_view = CurrentView::Library,
+                            InputGlobal::ViewPlaylist => {
+                                interface.current_view = CurrentView::Playlist;
+                                playlist.cursor = playlist.selected.min(playlist.list.len().saturating_sub(1));
+                            },
+                        });
+                    },
+                    StateActions::InputCommand(input) => {
+                        state.mutate(|interface, library, playlist| match input {
+                            InputCommand::Open      => interface.command = Some(ArrayString::new()),
+                            InputCommand::Char(c)   => if let Some(command) = interface.command.as_mut() {
+                                let _ = command.try_push(c);
+                            },
+                            InputCommand::Backspace => if let Some(command) = interface.command.as_mut() {
+                                command.pop();
+                            },
+                            InputCommand::Cancel    => interface.command = None,
+                            InputCommand::Submit    => {
+                                let command = interface.command.take().unwrap_or_default();
+                                let message = match run_command(&tx, library, playlist, &mut before_edit, &command) {
+                                    Ok(message) => message,
+                                    Err(err) => {
+                                        error!("{:?}", err);
+                                        err.chain().map(|err| err.to_string()).collect::<Vec<String>>().join(": ")
+                                    },
+                                };
+                                interface.set_status(&message, STATUS_DURATION);
+                                render_after(&tx, STATUS_DURATION);
                             },
                         });
                     },
                     StateActions::PlaybackNextTrack{error} => {
                         if let Some(err) = error {error!("{:?}", err)}
-                        state.mutate(|_, _, playlist| {
-                            playlist.next();
-                            if let Some(track) = playlist.get_next_track() {
-                                tx.playback.send(PlaybackActions::Que{track: Box::new(track)}).unwrap();
+                        state.mutate(|_, library, playlist| {
+                            let is_playable = |track: &TrackFile| !library.is_broken(track.id_track);
+                            playlist.next(is_playable);
+                            que_next(&tx, playlist, is_playable);
+                        });
+                    },
+                    StateActions::TrackBroken{track_id, error} => {
+                        error!("{:?}", error);
+                        state.mutate(|interface, library, playlist| {
+                            library.mark_broken(track_id);
+                            let title = library.tracks.iter()
+                                .find(|track| track.id_track == track_id)
+                                .map(|track| track.track_title.to_string())
+                                .unwrap_or_default();
+                            interface.set_status(&format!("Skipped {}: {}", title, error.root_cause()), STATUS_DURATION);
+
+                            let is_playable = |track: &TrackFile| !library.is_broken(track.id_track);
+                            match playlist.get_current_track().is_some_and(|track| track.id_track == track_id) {
+                                true => {
+                                    playlist.skip_unplayable(is_playable);
+                                    play_current(&tx, playlist, is_playable);
+                                },
+                                false => que_next(&tx, playlist, is_playable),
                             }
                         });
+                        render_after(&tx, STATUS_DURATION);
                     },
-                    StateActions::ScanAddSong{track} => state.mutate(|_, library, _| {
+                    StateActions::ScanAddSong{track, path} => state.mutate(|_, library, _| {
+                        library.paths.insert(track.id_track, path);
                         library.new_track(*track);
                         info!("{} tracks", library.tracks.len());
                     }),
+                    StateActions::ScanUpdateSong{track, path} => state.mutate(|_, library, playlist| {
+                        match library.paths.insert(track.id_track, path) {
+                            Some(_) => library.update_track(*track),
+                            None    => library.new_track(*track),
+                        }
+                        // the preloaded next track is queued again so playback picks up the changed file
+                        let is_playable = |track: &TrackFile| !library.is_broken(track.id_track);
+                        let is_next = playlist.get_next_track(is_playable).is_some_and(|next| next.id_track == track.id_track);
+                        if playlist.update_track(*track) && is_next {
+                            requeue_next(&tx, playlist, is_playable);
+                        }
+                    }),
+                    StateActions::ScanRemovePaths{paths} => state.mutate(|_, library, playlist| {
+                        // files are found by their id, only directories need a pass over all paths
+                        let (files, dirs) = paths.into_iter()
+                            .partition::<Vec<Box<Path>>, _>(|path| library.paths.contains_key(&TrackFile::id_for_path(path)));
+                        let mut removed = files.iter()
+                            .map(|path| TrackFile::id_for_path(path))
+                            .collect::<BTreeSet<u64>>();
+                        if !dirs.is_empty() {
+                            removed.extend(library.paths.iter()
+                                .filter(|(_, track_path)| dirs.iter().any(|dir| track_path.starts_with(dir)))
+                                .map(|(track_id, _)| *track_id));
+                        }
+                        removed.iter().for_each(|track_id| {library.paths.remove(track_id);});
+                        library.remove_tracks(&removed);
+                        info!("{} tracks", library.tracks.len());
+
+                        let is_playable = |track: &TrackFile| !library.is_broken(track.id_track);
+                        let next = playlist.get_next_track(is_playable).map(|track| track.id_track);
+                        playlist.remove_tracks(&removed);
+                        if playlist.get_next_track(is_playable).map(|track| track.id_track) != next {
+                            requeue_next(&tx, playlist, is_playable);
+                        }
+                    }),
+                    StateActions::ScanFinished => {
+                        if !is_restored {
+                            is_restored = true;
+                            state.mutate(|_, library, playlist| restore_session(&tx, library, playlist));
+                        }
+                    },
+                    StateActions::Exit{done} => {
+                        if is_restored {
+                            state.mutate(|_, library, playlist| if let Err(err) = SessionFile::save(library, playlist) {
+                                error!("Saving session: {:?}", err);
+                            });
+                        }
+                        let _ = done.send(());
+                    },
                     StateActions::Update() => {},
                     StateActions::Render() => render_queued = false,
                 };
 
+                if let Some(before) = before_edit {
+                    undo.record(before, state.playlist());
+                }
+
+                if state.playlist().radio {
+                    state.mutate(|_, library, playlist| extend_radio(&tx, library, playlist));
+                }
+
                 // Render change
                 match (render_queued, render_last.elapsed().as_millis()) {
                     (false, ..10) => {
@@ -170,3 +380,231 @@ fn state_loop(rx: Receiver<(Instant, StateActions)>, tx: MsgChannels) -> Result<
 //-////////////////////////////////////////////////////////////////////////////
 //
 //-////////////////////////////////////////////////////////////////////////////
+/// Starts playback from the current playlist track
+fn play_current(tx: &MsgChannels, playlist: &StatePlaylist, is_playable: impl Fn(&TrackFile) -> bool) {
+    if let Some(track) = playlist.get_current_track() {
+        let in_album = playlist.is_album_run(playlist.selected);
+        tx.playback.send(PlaybackActions::Play{track: Box::new(track), in_album, start_at: None}).unwrap();
+    }
+    que_next(tx, playlist, is_playable);
+}
+
+/// Queues the track that plays after the current one
+fn que_next(tx: &MsgChannels, playlist: &StatePlaylist, is_playable: impl Fn(&TrackFile) -> bool) {
+    if let Some(index) = playlist.get_next_index(is_playable) {
+        let track    = playlist.list[index].track;
+        let in_album = playlist.is_album_run(index);
+        tx.playback.send(PlaybackActions::Que{track: Box::new(track), in_album}).unwrap();
+    }
+}
+
+/// Replaces the queued track after the playlist order changed
+fn requeue_next(tx: &MsgChannels, playlist: &StatePlaylist, is_playable: impl Fn(&TrackFile) -> bool) {
+    if GlobalPlayback::state() == PlaybackState::Stopped {
+        return;
+    }
+    tx.playback.send(PlaybackActions::ClearUpcoming).unwrap();
+    que_next(tx, playlist, is_playable);
+}
+
+/// Appends related tracks in radio mode once nothing is left to play after the current track
+fn extend_radio(tx: &MsgChannels, library: &StateLibrary, playlist: &mut StatePlaylist) {
+    let is_playable = |track: &TrackFile| !library.is_broken(track.id_track);
+    if playlist.get_current_track().is_none() || playlist.get_next_index(is_playable).is_some() {
+        return;
+    }
+
+    let recent = playlist.recent_tracks(RADIO_AVOID.min(library.tracks.len() / 2).max(1));
+    let avoid  = recent.iter()
+        .chain(playlist.queued_tracks().iter())
+        .map(|track| track.id_track)
+        .chain(library.broken.keys().copied())
+        .collect::<BTreeSet<u64>>();
+    let seeds  = &recent[..RADIO_SEEDS.min(recent.len())];
+    let tracks = pick_radio_tracks(&library.tracks, seeds, &avoid, RADIO_BATCH);
+    if tracks.is_empty() {
+        return;
+    }
+    playlist.append(tracks);
+    requeue_next(tx, playlist, is_playable);
+}
+
+/// Playlist view input, edits are mirrored to the playback queue
+fn playlist_input(tx: &MsgChannels, playlist: &mut StatePlaylist, is_playable: impl Fn(&TrackFile) -> bool, input: InputLocal) {
+    let index = playlist.cursor;
+    match input {
+        InputLocal::Up     => playlist.cursor_up(1),
+        InputLocal::Down   => playlist.cursor_down(1),
+        InputLocal::PgUp   => playlist.cursor_up(10),
+        InputLocal::PgDown => playlist.cursor_down(10),
+        InputLocal::Home   => playlist.cursor_start(),
+        InputLocal::End    => playlist.cursor_end(),
+        InputLocal::Select => {
+            playlist.jump(index);
+            playlist.skip_unplayable(&is_playable);
+            tx.playback.send(PlaybackActions::Clear).unwrap();
+            play_current(tx, playlist, is_playable);
+        },
+        InputLocal::MoveUp | InputLocal::MoveDown => {
+            let next = playlist.get_next_track(&is_playable).map(|track| track.id_track);
+            playlist.move_entry(index, matches!(input, InputLocal::MoveUp));
+            if playlist.get_next_track(&is_playable).map(|track| track.id_track) != next {
+                requeue_next(tx, playlist, is_playable);
+            }
+        },
+        InputLocal::Remove => {
+            let is_current = index == playlist.selected;
+            playlist.remove(index);
+            match is_current {
+                true if GlobalPlayback::state() != PlaybackState::Stopped => {
+                    playlist.skip_unplayable(&is_playable);
+                    tx.playback.send(PlaybackActions::Clear).unwrap();
+                    play_current(tx, playlist, is_playable);
+                },
+                true  => {},
+                false => requeue_next(tx, playlist, is_playable),
+            }
+        },
+        InputLocal::ClearPlayed => playlist.clear_played(),
+        InputLocal::Left      |
+        InputLocal::Right     |
+        InputLocal::Tab        |
+        InputLocal::RevTab     |
+        InputLocal::SelectAlt  |
+        InputLocal::SelectNext => {},
+    }
+}
+
+/// Runs a command typed after `:`, returns the message for the status bar.
+/// Commands that replace the queue leave the queue from before in `before_edit`.
+fn run_command(tx: &MsgChannels, library: &StateLibrary, playlist: &mut StatePlaylist, before_edit: &mut Option<PlaylistSnapshot>, command: &str) -> Result<String> {
+    let (name, argument) = command.trim().split_once(' ').unwrap_or((command.trim(), ""));
+    let path = || expand_home_dir(&BaseDirs::new(), PathBuf::from(argument.trim()));
+    match (name, argument.trim().is_empty()) {
+        ("", _) => Ok(String::new()),
+        ("load", false) => {
+            let path = path()?;
+            let (tracks, missing) = library.tracks_for_playlist_file(read_playlist_file(&path)?);
+            for entry in &missing {
+                match entry.path.exists() {
+                    true  => warn!("Playlist entry not in library: {}", entry.path.to_string_lossy()),
+                    false => warn!("Playlist entry missing: {}", entry.path.to_string_lossy()),
+                }
+            }
+
+            let is_playable = |track: &TrackFile| !library.is_broken(track.id_track);
+            let count = tracks.len();
+            *before_edit = Some(PlaylistSnapshot::take(playlist));
+            playlist.replace(tracks, 0);
+            playlist.skip_unplayable(is_playable);
+            tx.playback.send(PlaybackActions::Clear).unwrap();
+            play_current(tx, playlist, is_playable);
+
+            Ok(match missing.first() {
+                None        => format!("Loaded {} tracks", count),
+                Some(first) => format!(
+                    "Loaded {} tracks, {} not found: {}",
+                    count,
+                    missing.len(),
+                    first.path.to_string_lossy(),
+                ),
+            })
+        },
+        ("save", false) => {
+            let path    = path()?;
+            let tracks  = playlist.list.iter().map(|entry| entry.track).collect::<Vec<TrackFile>>();
+            let entries = library.playlist_file_entries(&tracks);
+            write_playlist_file(&path, &entries)?;
+            Ok(format!("Saved {} tracks to {}", entries.len(), path.to_string_lossy()))
+        },
+        ("rescan", _) => Ok(rescan(tx)),
+        ("load" | "save", true) => bail!("Missing playlist path: {} <path>", name),
+        (_, _) => bail!("Unknown command: {}", name),
+    }
+}
+
+/// Starts a rescan of the media dirs, returns the message for the status bar
+fn rescan(tx: &MsgChannels) -> String {
+    match tx.scan.try_send(ScanActions::Rescan) {
+        Ok(()) => "Rescanning media dirs".to_string(),
+        Err(_) => "Rescan already queued".to_string(),
+    }
+}
+
+/// Playlist view inputs that change the queue, the queue is compared before and after these for undo
+fn is_queue_edit(input: &InputLocal, playlist: &StatePlaylist) -> bool {
+    let index = playlist.cursor;
+    match input {
+        InputLocal::MoveUp      => index > 0 && index < playlist.list.len(),
+        InputLocal::MoveDown    => index + 1 < playlist.list.len(),
+        InputLocal::Remove      => index < playlist.list.len(),
+        InputLocal::ClearPlayed => playlist.has_played(),
+        _ => false,
+    }
+}
+
+/// Puts back a queue from the undo history, playback restarts only if the current track changed
+fn restore_snapshot(tx: &MsgChannels, playlist: &mut StatePlaylist, snapshot: PlaylistSnapshot, is_playable: impl Fn(&TrackFile) -> bool) {
+    let current = |playlist: &StatePlaylist| playlist.get_current_track().map(|track| (playlist.selected, track.id_track));
+    let current_before = current(playlist);
+    playlist.restore(snapshot.queue);
+
+    let track = match playlist.get_current_track() {
+        None => {
+            tx.playback.send(PlaybackActions::Clear).unwrap();
+            return;
+        },
+        Some(_) if current(playlist) == current_before && GlobalPlayback::state() != PlaybackState::Stopped => {
+            return requeue_next(tx, playlist, is_playable);
+        },
+        Some(track) => track,
+    };
+
+    let restore_position = CONFIG.get().unwrap().playback.undo_restores_position;
+    let (start_at, paused) = match (restore_position, snapshot.state) {
+        (true, PlaybackState::Playing) => (Some(snapshot.elapsed), false),
+        (true, PlaybackState::Paused ) => (Some(snapshot.elapsed), true),
+        (_   , _                     ) => (None, false),
+    };
+    let in_album = playlist.is_album_run(playlist.selected);
+    tx.playback.send(PlaybackActions::Clear).unwrap();
+    tx.playback.send(PlaybackActions::Play{track: Box::new(track), in_album, start_at}).unwrap();
+    if paused {
+        tx.playback.send(PlaybackActions::Pause).unwrap();
+    }
+    que_next(tx, playlist, is_playable);
+}
+
+/// Restores the previous session, the queue only if nothing has been queued during the scan
+fn restore_session(tx: &MsgChannels, library: &mut StateLibrary, playlist: &mut StatePlaylist) {
+    let session = match SessionFile::load() {
+        Ok(Some(session)) => session,
+        Ok(None) => return,
+        Err(err) => return error!("Loading session: {:?}", err),
+    };
+    session.restore_library(library);
+    if !playlist.list.is_empty() {
+        return;
+    }
+
+    let playback = session.restore_playlist(library, playlist);
+    let is_playable = |track: &TrackFile| !library.is_broken(track.id_track);
+    if let (Some(playback), Some(track)) = (playback, playlist.get_current_track()) {
+        let in_album = playlist.is_album_run(playlist.selected);
+        tx.playback.send(PlaybackActions::Play{track: Box::new(track), in_album, start_at: Some(playback.start_at)}).unwrap();
+        if playback.paused || CONFIG.get().unwrap().playback.resume_paused {
+            tx.playback.send(PlaybackActions::Pause).unwrap();
+        }
+        que_next(tx, playlist, is_playable);
+    }
+}
+
+/// Renders again once `duration` has passed, for content that expires
+fn render_after(tx: &MsgChannels, duration: Duration) {
+    if let Err(err) = tx.update.send(UpdateActions::Expire(duration)) {
+        error!("Scheduling render: {:?}", err);
+    }
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
@@ -1,12 +1,15 @@
 use crate::config::conf_theme::ConfTheme;
 use crate::globals::playback_state::GlobalPlaybackSnapshot;
 use crate::globals::terminal_state::GlobalUiStateSnapshot;
-use crate::state::state_playlist::StatePlaylist;
+use crate::state::state_playlist::PlaylistStates;
 use crate::types::types_tui::TermState;
 use crate::types::types_msg_channels::MsgChannels;
 use crate::ui::views::view_library::draw_library_view;
 use crate::ui::views::view_library::RenderDataViewLibrary;
+use crate::ui::views::view_playlist::draw_playlist_view;
+use crate::ui::views::view_playlist::RenderDataViewPlaylist;
 use crate::ui::widgets::widget_playback_status::render_playback_status_widget;
+use arrayvec::ArrayString;
 use color_eyre::eyre::Context;
 use color_eyre::Result;
 use crossbeam_channel::Receiver;
@@ -18,6 +21,7 @@ use crossterm::style::Print;
 use crossterm::terminal;
 use std::io::stdout;
 use std::io::Stdout;
+use std::sync::Arc;
 use std::time::Duration;
 use std::time::Instant;
 
@@ -41,12 +45,15 @@ pub struct RenderDataCommon {
     pub theme: ConfTheme,
     pub term: GlobalUiStateSnapshot,
     pub playback: GlobalPlaybackSnapshot,
-    pub playlist: StatePlaylist,
+    pub playlist: Arc<PlaylistStates>,
+    pub status: Option<ArrayString<128>>,
+    pub command: Option<ArrayString<256>>,
 }
 
 #[derive(Debug)]
 pub enum RenderDataView {
     Library(RenderDataViewLibrary),
+    Playlist(RenderDataViewPlaylist),
 }
 
 pub fn start_tui_listener(tx: MsgChannels, tx_tui_done: Sender<()>, rx: Receiver<RenderActions>) {
@@ -109,7 +116,8 @@ fn render_loop(stdout: &mut Stdout, rx: Receiver<RenderActions>) -> Result<()> {
 
                     // render view
                     match view {
-                        RenderDataView::Library(view) => draw_library_view(&mut term_state, &common, view),
+                        RenderDataView::Library(view)  => draw_library_view(&mut term_state, &common, view),
+                        RenderDataView::Playlist(view) => draw_playlist_view(&mut term_state, &common, view),
                     }
 
                     // render playback status
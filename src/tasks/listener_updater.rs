@@ -4,6 +4,7 @@ use crate::types::types_msg_channels::MsgChannels;
 use crate::tasks::listener_state::StateActions;
 use crate::ui::utils::ui_loading_icon_util::next_loading_rotation_interval;
 use crossbeam_channel::Receiver;
+use crossbeam_channel::RecvTimeoutError;
 use std::sync::atomic::AtomicBool;
 use std::thread::sleep;
 use std::time::Duration;
@@ -17,6 +18,8 @@ pub enum UpdateActions {
     Playback(bool),
     LoadingLibrary(bool),
     LoadingTrack(bool),
+    /// Content on screen expires after this, one update is sent once it has
+    Expire(Duration),
 }
 
 #[derive(PartialEq, Eq)]
@@ -37,12 +40,30 @@ pub fn start_updater(tx: MsgChannels, rx: Receiver<UpdateActions>) {
     let mut playback        = false;
     let mut loading_library = false;
     let mut loading_track   = false;
+    let mut expire_at: Option<Instant> = None;
 
     loop {
-        match rx.recv().unwrap() {
+        let action = match expire_at {
+            None     => rx.recv().unwrap(),
+            Some(at) => match rx.recv_timeout(at.saturating_duration_since(Instant::now())) {
+                Ok(action) => action,
+                Err(RecvTimeoutError::Timeout) => {
+                    expire_at = None;
+                    tx.state.send((Instant::now(), StateActions::Update())).unwrap();
+                    continue;
+                },
+                Err(RecvTimeoutError::Disconnected) => return,
+            },
+        };
+        match action {
             UpdateActions::Playback(run)       => playback        = run,
             UpdateActions::LoadingLibrary(run) => loading_library = run,
             UpdateActions::LoadingTrack(run)   => loading_track   = run,
+            UpdateActions::Expire(duration)    => {
+                let at = Instant::now() + duration;
+                expire_at = Some(expire_at.map_or(at, |expire_at| expire_at.max(at)));
+                continue;
+            },
         };
 
         clock_loop(playback, &tx);
@@ -70,7 +91,7 @@ fn clock_loop(run: bool, tx: &MsgChannels) {
                             break;
                         }
 
-                        let elapsed  = GlobalPlayback::elapsed().as_secs_f64();
+                        let elapsed  = GlobalPlayback::snapshot().elapsed.as_secs_f64();
                         let interval = 1.0;
                         let next     = interval - (elapsed % interval);
 
@@ -100,8 +121,9 @@ fn progress_loop(run: bool, tx: &MsgChannels) {
                             break;
                         }
 
-                        let duration = GlobalPlayback::duration().as_secs_f64();
-                        let elapsed  = GlobalPlayback::elapsed().as_secs_f64();
+                        let playback = GlobalPlayback::snapshot();
+                        let duration = playback.duration.as_secs_f64();
+                        let elapsed  = playback.elapsed.as_secs_f64();
                         let width    = GlobalUiState::progress_width() as f64;
 
                         let interval = duration / width;
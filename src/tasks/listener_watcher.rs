@@ -0,0 +1,142 @@
+use crate::tasks::listener_playback::PlaybackActions;
+use crate::tasks::listener_scanner::is_track_file;
+use crate::tasks::listener_state::StateActions;
+use crate::types::types_library_entry::TrackFile;
+use crate::types::types_msg_channels::MsgChannels;
+use crate::CONFIG;
+use color_eyre::eyre::OptionExt;
+use color_eyre::Result;
+use crossbeam_channel::unbounded;
+use crossbeam_channel::RecvTimeoutError;
+use notify::event::AccessKind;
+use notify::event::AccessMode;
+use notify::event::EventKind;
+use notify::event::ModifyKind;
+use notify::recommended_watcher;
+use notify::Event;
+use notify::RecursiveMode;
+use notify::Watcher;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+/// quiet time after the last change before changed files are read, files are often written in many steps
+const DEBOUNCE: Duration = Duration::from_millis(500);
+/// longest a change waits while changes keep coming
+const DEBOUNCE_MAX: Duration = Duration::from_secs(5);
+
+pub fn start_fs_watcher_listener(tx: MsgChannels) {
+    // the library still works without watching, e.g. when the inotify watch limit is reached
+    if let Err(err) = watcher_loop(&tx) {
+        error!("watch error: {:?}", err);
+    }
+}
+
+fn watcher_loop(tx: &MsgChannels) -> Result<()> {
+    let dirs = &CONFIG.get().ok_or_eyre("Config not initialized")?.media_dirs;
+
+    let (tx_events, rx_events) = unbounded();
+    let mut watcher = recommended_watcher(move |event: notify::Result<Event>| {
+        let _ = tx_events.send(event);
+    })?;
+    for dir in dirs.iter() {
+        match watcher.watch(dir, RecursiveMode::Recursive) {
+            Ok(()) => info!("Watching '{:?}'", dir),
+            Err(err) => warn!("Watching {:?}: {:?}", dir, err),
+        }
+    }
+
+    let mut changed = BTreeSet::new();
+    let mut first_change = Instant::now();
+    loop {
+        let timeout = match changed.is_empty() {
+            true  => Duration::MAX,
+            false => DEBOUNCE.min(DEBOUNCE_MAX.saturating_sub(first_change.elapsed())),
+        };
+        match rx_events.recv_timeout(timeout) {
+            Ok(Ok(event)) => {
+                if !is_change(event.kind) {
+                    continue;
+                }
+                if changed.is_empty() {
+                    first_change = Instant::now();
+                }
+                changed.extend(event.paths.into_iter().filter(|path| !is_hidden(dirs, path)));
+            },
+            Ok(Err(err)) => warn!("Watch error: {:?}", err),
+            Err(RecvTimeoutError::Timeout) => {
+                info!("{} changed paths", changed.len());
+                let mut removed = vec![];
+                for path in std::mem::take(&mut changed) {
+                    apply_change(tx, path, &mut removed);
+                }
+                if !removed.is_empty() {
+                    tx.state.send((Instant::now(), StateActions::ScanRemovePaths{paths: removed}))?;
+                }
+            },
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    }
+}
+
+/// Events that can change which tracks exist or what their tags are
+fn is_change(kind: EventKind) -> bool {
+    match kind {
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+        EventKind::Access(_) => false,
+        EventKind::Modify(ModifyKind::Metadata(_)) => false,
+        EventKind::Create(_) |
+        EventKind::Modify(_) |
+        EventKind::Remove(_) |
+        EventKind::Any       => true,
+        EventKind::Other     => false,
+    }
+}
+
+/// Below a dot directory inside a media dir, the scan skips those too
+fn is_hidden(dirs: &[PathBuf], path: &Path) -> bool {
+    dirs.iter()
+        .filter_map(|dir| path.strip_prefix(dir).ok())
+        .any(|relative| relative.iter().any(|name| name.to_string_lossy().starts_with(".")))
+}
+
+/// Reads a changed path, paths that are gone are collected in `removed` to go with everything below them
+fn apply_change(tx: &MsgChannels, path: PathBuf, removed: &mut Vec<Box<Path>>) {
+    if path.is_dir() {
+        if let Ok(dir) = fs::read_dir(&path) {
+            dir.filter_map(|entry| entry.ok())
+                .filter(|entry| !entry.file_name().to_string_lossy().starts_with("."))
+                .for_each(|entry| apply_change(tx, entry.path(), removed));
+        }
+        return;
+    }
+    if path.is_file() {
+        if !is_track_file(&path) {
+            return;
+        }
+        match TrackFile::new(&path) {
+            Ok(track) => {
+                tx.state
+                    .send((Instant::now(), StateActions::ScanUpdateSong{track: Box::new(track), path: path.clone().into_boxed_path()}))
+                    .unwrap();
+                tx.playback
+                    .send(PlaybackActions::NewTrack{track_id: track.id_track, path: path.into_boxed_path()})
+                    .unwrap();
+            },
+            Err(e) => error!("Parse track error: {:?} {:?}", path, e),
+        }
+        return;
+    }
+    if !path.exists() {
+        removed.push(path.into_boxed_path());
+    }
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
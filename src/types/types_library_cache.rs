@@ -0,0 +1,194 @@
+use crate::functions::functions_data_file::read_cache_file;
+use crate::functions::functions_data_file::write_cache_file;
+use crate::types::types_library_entry::str_trunc;
+use crate::types::types_library_entry::ReplayGain;
+use crate::types::types_library_entry::TrackFile;
+use crate::types::types_library_entry::GENRE_LIMIT;
+use crate::CONFIG;
+use color_eyre::Result;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::Metadata;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::UNIX_EPOCH;
+
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+const CACHE_FILE: &str = "library.bin";
+/// cache files written with another version are ignored, bump when the cached fields change
+const CACHE_VERSION: u32 = 2;
+
+/// Scanned tracks from the previous run, so that unchanged files do not have to be parsed again
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct LibraryCache {
+    version: u32,
+    /// genres were split on these, other separators need the files parsed again
+    genre_separators: Vec<String>,
+    tracks : Vec<CachedTrack>,
+}
+
+/// Tags of a file, valid as long as the size and modified time of the file stay the same
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct CachedTrack {
+    pub path: PathBuf,
+    size       : u64,
+    modified_ns: Option<u64>,
+
+    duration_ms : u64,
+    year        : Option<u16>,
+    album_artist: Option<String>,
+    album_title : Option<String>,
+    album_number: Option<u8>,
+    track_artist: Option<String>,
+    track_title : String,
+    track_number: Option<u8>,
+    genres      : Vec<String>,
+    track_gain  : Option<f32>,
+    track_peak  : Option<f32>,
+    album_gain  : Option<f32>,
+    album_peak  : Option<f32>,
+}
+
+impl LibraryCache {
+    /// Cached tracks by path, empty when there is no usable cache
+    pub fn load() -> Result<BTreeMap<PathBuf, CachedTrack>> {
+        let cache = match read_cache_file::<LibraryCache>(CACHE_FILE)? {
+            Some(cache) if cache.version == CACHE_VERSION && cache.genre_separators == genre_separators() => cache,
+            Some(_) | None => return Ok(BTreeMap::new()),
+        };
+        Ok(cache.tracks.into_iter().map(|track| (track.path.clone(), track)).collect())
+    }
+
+    /// Replaces the cache, files that were not found in the scan are left out
+    pub fn save(tracks: Vec<CachedTrack>) -> Result<()> {
+        write_cache_file(CACHE_FILE, &LibraryCache{version: CACHE_VERSION, genre_separators: genre_separators(), tracks})
+    }
+}
+
+impl CachedTrack {
+    pub fn new(path: &Path, meta: &Metadata, track: &TrackFile) -> CachedTrack {
+        CachedTrack{
+            path: path.to_path_buf(),
+            size: meta.len(),
+            modified_ns: modified_ns(meta),
+
+            duration_ms : track.duration.as_millis() as u64,
+            year        : track.year,
+            album_artist: track.album_artist.map(|s| s.to_string()),
+            album_title : track.album_title.map(|s| s.to_string()),
+            album_number: track.album_number,
+            track_artist: track.track_artist.map(|s| s.to_string()),
+            track_title : track.track_title.to_string(),
+            track_number: track.track_number,
+            genres      : track.genres.iter().flatten().map(|genre| genre.to_string()).collect(),
+            track_gain  : track.replay_gain.track_gain,
+            track_peak  : track.replay_gain.track_peak,
+            album_gain  : track.replay_gain.album_gain,
+            album_peak  : track.replay_gain.album_peak,
+        }
+    }
+
+    /// The file has not been changed since it was cached
+    pub fn is_current(&self, meta: &Metadata) -> bool {
+        self.size == meta.len() && self.modified_ns.is_some() && self.modified_ns == modified_ns(meta)
+    }
+
+    pub fn track(&self) -> TrackFile {
+        let album_artist = self.album_artist.as_deref().map(str_trunc);
+        let album_title  = self.album_title.as_deref().map(str_trunc);
+        let (id_artist, id_album) = TrackFile::id_for_album(album_artist, album_title);
+        let mut genres = [None; GENRE_LIMIT];
+        genres.iter_mut().zip(self.genres.iter()).for_each(|(slot, genre)| *slot = Some(str_trunc(genre)));
+        TrackFile{
+            is_album_padding: false,
+            id_artist,
+            id_album,
+            id_track: TrackFile::id_for_path(&self.path),
+            modified: self.modified_ns.map(|ns| UNIX_EPOCH + Duration::from_nanos(ns)),
+
+            duration: Duration::from_millis(self.duration_ms),
+            year: self.year,
+            album_artist,
+            album_title,
+            album_number: self.album_number,
+            track_artist: self.track_artist.as_deref().map(str_trunc),
+            track_title : str_trunc(&self.track_title),
+            track_number: self.track_number,
+            genres,
+            genre_ids   : TrackFile::ids_for_genres(&genres),
+            replay_gain : ReplayGain{
+                track_gain: self.track_gain,
+                track_peak: self.track_peak,
+                album_gain: self.album_gain,
+                album_peak: self.album_peak,
+            },
+        }
+    }
+}
+
+fn genre_separators() -> Vec<String> {
+    CONFIG.get().map(|config| config.library.genre_separators.clone()).unwrap_or_default()
+}
+
+fn modified_ns(meta: &Metadata) -> Option<u64> {
+    meta.modified().ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_nanos() as u64)
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs::metadata;
+
+    #[test]
+    fn test_cached_track_roundtrip() {
+        let path = std::env::temp_dir().join("music_player_cache_test.flac");
+        std::fs::write(&path, b"not really flac").unwrap();
+        let meta = metadata(&path).unwrap();
+
+        let track = CachedTrack{
+            path: path.clone(),
+            size: 0,
+            modified_ns: None,
+            duration_ms : 61_500,
+            year        : Some(1999),
+            album_artist: Some("Artist".to_string()),
+            album_title : Some("Album".to_string()),
+            album_number: Some(1),
+            track_artist: None,
+            track_title : "Title".to_string(),
+            track_number: Some(3),
+            genres      : vec!["Rock".to_string(), "Blues".to_string()],
+            track_gain  : Some(-6.5),
+            track_peak  : None,
+            album_gain  : None,
+            album_peak  : Some(0.9),
+        }.track();
+        let cached = CachedTrack::new(&path, &meta, &track);
+        assert!(cached.is_current(&meta));
+        assert_eq!(cached.track().id_track, track.id_track);
+        assert_eq!(cached.track().id_album, track.id_album);
+        assert_eq!(cached.track().modified, meta.modified().ok());
+
+        assert_eq!(cached.track().genres.iter().flatten().count(), 2);
+
+        let cache = LibraryCache{version: CACHE_VERSION, genre_separators: vec![], tracks: vec![cached.clone()]};
+        let parsed: LibraryCache = postcard::from_bytes(&postcard::to_stdvec(&cache).unwrap()).unwrap();
+        assert_eq!(parsed.tracks, vec![cached]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,5 +1,6 @@
 use crate::functions::functions_hash::hash;
 use crate::traits::trait_listable::Listable;
+use crate::CONFIG;
 use arrayvec::ArrayString;
 use color_eyre::eyre::OptionExt;
 use color_eyre::Result;
@@ -9,13 +10,24 @@ use lofty::prelude::ItemKey;
 use lofty::read_from_path;
 use lofty::tag::Accessor;
 use std::cmp::Ordering;
+use std::fs::metadata;
 use std::path::Path;
 use std::str::FromStr;
 use std::time::Duration;
+use std::time::SystemTime;
 
 //-////////////////////////////////////////////////////////////////////////////
 //  Raw Entry
 //-////////////////////////////////////////////////////////////////////////////
+/// genres kept per track, the rest of a genre tag is dropped
+pub const GENRE_LIMIT: usize = 4;
+
+pub type Genres = [Option<ArrayString<32>>; GENRE_LIMIT];
+/// ids of `Genres`, kept on the track so that genre filters do not hash names
+pub type GenreIds = [Option<u64>; GENRE_LIMIT];
+/// genre filter entry for tracks without a genre tag
+pub const NO_GENRE_ID: u64 = 0;
+
 #[derive(Clone, Copy)]
 #[derive(Debug)]
 pub struct TrackFile {
@@ -24,6 +36,7 @@ pub struct TrackFile {
     pub id_artist : u64,
     pub id_album  : u64,
     pub id_track  : u64, // hash of file path
+    pub modified  : Option<SystemTime>,
 
     pub duration     : Duration,
     pub year         : Option<u16>,
@@ -33,12 +46,55 @@ pub struct TrackFile {
     pub track_artist : Option<ArrayString<64>>,
     pub track_title  : ArrayString<128>,
     pub track_number : Option<u8>,
+    pub genres       : Genres,
+    pub genre_ids    : GenreIds,
+    pub replay_gain  : ReplayGain,
+}
+
+/// ReplayGain tags, gain in dB and peak as linear sample amplitude
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct ReplayGain {
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+impl ReplayGain {
+    /// Linear multiplier for playback, `preamp_untagged` in dB is used when the track has no gain tags.
+    /// Album values fall back to track values and the other way around.
+    /// Limited by the peak so that the loudest sample does not clip.
+    pub fn multiplier(&self, album: bool, preamp_untagged: f32) -> f32 {
+        let (gain, peak) = match album {
+            true  => (self.album_gain.or(self.track_gain), self.album_peak.or(self.track_peak)),
+            false => (self.track_gain.or(self.album_gain), self.track_peak.or(self.album_peak)),
+        };
+        let multiplier = 10f32.powf(gain.unwrap_or(preamp_untagged) / 20.0);
+        match peak.filter(|peak| *peak > 0.0) {
+            Some(peak) => multiplier.min(1.0 / peak),
+            None       => multiplier,
+        }
+    }
 }
 
-fn str_trunc<const L: usize>(text: &str) -> ArrayString<L> {
+/// Parses tag values like `-6.54 dB` or `0.988525`
+fn parse_replay_gain(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let value = value.strip_suffix("dB")
+        .or(value.strip_suffix("db"))
+        .or(value.strip_suffix("DB"))
+        .unwrap_or(value);
+    value.trim().parse::<f32>().ok().filter(|v| v.is_finite())
+}
+
+pub fn str_trunc<const L: usize>(text: &str) -> ArrayString<L> {
     let mut arr = ArrayString::<L>::new();
     if text.len() > L {
-        arr.push_str(&text[..L-1]);
+        // cut on a character boundary, multibyte characters are never split
+        let end = (0..L).rev().find(|end| text.is_char_boundary(*end)).unwrap_or_default();
+        arr.push_str(&text[..end]);
         arr.push_str(">");
     } else {
         arr.push_str(&text[..L.min(text.len())]);
@@ -48,7 +104,8 @@ fn str_trunc<const L: usize>(text: &str) -> ArrayString<L> {
 
 impl TrackFile {
     pub fn new(path: &Path) -> Result<TrackFile> {
-        let file = read_from_path(path)?;
+        let file     = read_from_path(path)?;
+        let modified = metadata(path).and_then(|meta| meta.modified()).ok();
 
         let properties = file.properties();
         let primary    = file.primary_tag().ok_or_eyre("primary tags not found")?;
@@ -63,16 +120,27 @@ impl TrackFile {
         let album_artist = primary.get_string(&ItemKey::AlbumArtist).filter(|s| !s.is_empty()).map(|s| str_trunc(&s)).or(track_artist);
         let album_title  = primary.album().filter(|s| !s.is_empty()).map(|s| str_trunc(&s));
         let album_number = primary.disk().map(|n| n as u8);
+        let genres       = TrackFile::genres_from_tag(&primary.get_strings(&ItemKey::Genre).collect::<Vec<&str>>().join("\0"));
 
-        let id_artist = hash(&album_artist.unwrap_or_default().to_lowercase());
-        let id_album  = hash(&(id_artist, album_title.unwrap_or_default().to_lowercase()));
-        let id_track  = hash(&path.to_string_lossy().to_lowercase());
+        // gain tags are not always in the primary tag, e.g. ape tags next to id3 in mp3 files
+        let replay_gain_tag = |key: ItemKey| file.tags().iter()
+            .find_map(|tag| tag.get_string(&key).and_then(parse_replay_gain));
+        let replay_gain = ReplayGain{
+            track_gain: replay_gain_tag(ItemKey::ReplayGainTrackGain),
+            track_peak: replay_gain_tag(ItemKey::ReplayGainTrackPeak),
+            album_gain: replay_gain_tag(ItemKey::ReplayGainAlbumGain),
+            album_peak: replay_gain_tag(ItemKey::ReplayGainAlbumPeak),
+        };
+
+        let (id_artist, id_album) = TrackFile::id_for_album(album_artist, album_title);
+        let id_track = TrackFile::id_for_path(path);
 
         Ok(TrackFile{
             is_album_padding: false,
             id_artist,
             id_album,
             id_track,
+            modified,
 
             duration,
             year,
@@ -82,9 +150,74 @@ impl TrackFile {
             track_artist,
             track_title,
             track_number,
+            genres,
+            genre_ids: TrackFile::ids_for_genres(&genres),
+            replay_gain,
         })
     }
 
+    /// Track id for a file path, the same path always maps to the same track
+    pub fn id_for_path(path: &Path) -> u64 {
+        hash(&path.to_string_lossy().to_lowercase())
+    }
+
+    /// Artist and album id from the album artist and album title tags
+    pub fn id_for_album(album_artist: Option<ArrayString<64>>, album_title: Option<ArrayString<64>>) -> (u64, u64) {
+        let id_artist = hash(&album_artist.unwrap_or_default().to_lowercase());
+        let id_album  = hash(&(id_artist, album_title.unwrap_or_default().to_lowercase()));
+        (id_artist, id_album)
+    }
+
+    /// Splits a genre tag on the configured separators, duplicates and empty genres are left out
+    pub fn genres_from_tag(tag: &str) -> Genres {
+        let separators = CONFIG.get().map(|config| config.library.genre_separators.as_slice()).unwrap_or_default();
+        let mut genres: Vec<&str> = vec![tag];
+        for separator in separators.iter().map(|s| s.as_str()).chain(["\0"]).filter(|s| !s.is_empty()) {
+            genres = genres.into_iter().flat_map(|genre| genre.split(separator)).collect();
+        }
+
+        let mut result: Genres = [None; GENRE_LIMIT];
+        let mut ids = vec![];
+        for genre in genres.into_iter().map(|genre| genre.trim()).filter(|genre| !genre.is_empty()) {
+            let id = LibraryGenreEntry::id_for_name(genre);
+            if ids.contains(&id) {
+                continue;
+            }
+            match result.iter_mut().find(|slot| slot.is_none()) {
+                Some(slot) => *slot = Some(str_trunc(genre)),
+                None => break,
+            }
+            ids.push(id);
+        }
+        result
+    }
+
+    pub fn ids_for_genres(genres: &Genres) -> GenreIds {
+        genres.map(|genre| genre.map(|genre| LibraryGenreEntry::id_for_name(&genre)))
+    }
+
+    /// Genre filter entries of the track, a single entry without a name when the track has no genre
+    pub fn genre_entries(&self) -> Vec<LibraryGenreEntry> {
+        let entries = self.genres.iter()
+            .flatten()
+            .map(|genre| LibraryGenreEntry::new(Some(*genre)))
+            .collect::<Vec<LibraryGenreEntry>>();
+        match entries.is_empty() {
+            true  => vec![LibraryGenreEntry::new(None)],
+            false => entries,
+        }
+    }
+
+    /// Ids of the genre filters the track is listed under, like `genre_entries` without the names
+    pub fn genre_filter_ids(&self) -> impl Iterator<Item = u64> {
+        let untagged = self.genre_ids[0].is_none().then_some(NO_GENRE_ID);
+        self.genre_ids.into_iter().flatten().chain(untagged)
+    }
+
+    pub fn has_genre(&self, genre_id: u64) -> bool {
+        self.genre_filter_ids().any(|id| id == genre_id)
+    }
+
     fn compare_values(&self) -> (Option<u16>, Option<String>, Option<String>, Option<u8>, bool, Option<u8>) {
         (
             self.year,
@@ -97,6 +230,39 @@ impl TrackFile {
     }
 }
 
+#[cfg(test)]
+impl TrackFile {
+    /// Untagged one minute track, tests override fields with `TrackFile{year: Some(2000), ..TrackFile::test(1)}`
+    pub fn test(id_track: u64) -> TrackFile {
+        TrackFile{
+            is_album_padding: false,
+            id_artist: 0,
+            id_album: 0,
+            id_track,
+            modified: None,
+            duration: Duration::from_secs(60),
+            year: None,
+            album_artist: None,
+            album_title: None,
+            album_number: None,
+            track_artist: None,
+            track_title: ArrayString::new(),
+            track_number: None,
+            genres: Default::default(),
+            genre_ids: Default::default(),
+            replay_gain: Default::default(),
+        }
+    }
+
+    /// Album tags along with the ids that are derived from them
+    pub fn with_album(self, artist: &str, title: &str) -> TrackFile {
+        let album_artist = ArrayString::from(artist).ok();
+        let album_title  = ArrayString::from(title).ok();
+        let (id_artist, id_album) = TrackFile::id_for_album(album_artist, album_title);
+        TrackFile{id_artist, id_album, album_artist, album_title, ..self}
+    }
+}
+
 impl Listable for TrackFile {
     fn is_selectable(&self) -> bool {
         !self.is_album_padding
@@ -139,19 +305,20 @@ impl LibraryArtistEntry {
         LibraryArtistEntry{
             artist_id: track.id_artist,
             name_display: track.album_artist,
-            name_compare: track.album_artist.map(|aa| {
-                let lower = aa.to_lowercase();
-                let mut arr = ArrayString::new();
-                match lower.starts_with("the ") {
-                    true => arr.push_str(&lower[4..]),
-                    false => arr.push_str(&lower),
-                };
-                arr
-            }),
+            name_compare: track.album_artist.map(|aa| name_compare(&aa)),
         }
     }
 }
 
+/// Lowercase name without a leading article, so "The Band" sorts under b
+fn name_compare(name: &str) -> ArrayString<64> {
+    let lower = name.to_lowercase();
+    match lower.starts_with("the ") {
+        true  => str_trunc(&lower[4..]),
+        false => str_trunc(&lower),
+    }
+}
+
 impl PartialEq for LibraryArtistEntry {
     fn eq(&self, other: &Self) -> bool {
         self.artist_id == other.artist_id
@@ -172,27 +339,139 @@ impl Ord for LibraryArtistEntry {
     }
 }
 
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+pub struct LibraryAlbumEntry {
+    pub album_id: u64,
+    pub title_compare : Option<ArrayString<64>>,
+    pub artist_compare: Option<ArrayString<64>>,
+    pub title_display : Option<ArrayString<64>>,
+    pub artist_display: Option<ArrayString<64>>,
+    pub year: Option<u16>,
+}
+
+impl LibraryAlbumEntry {
+    pub fn from_track(track: TrackFile) -> LibraryAlbumEntry {
+        LibraryAlbumEntry{
+            album_id: track.id_album,
+            title_compare : track.album_title.map(|title| name_compare(&title)),
+            artist_compare: track.album_artist.map(|artist| name_compare(&artist)),
+            title_display : track.album_title,
+            artist_display: track.album_artist,
+            year: track.year,
+        }
+    }
+
+    fn compare_values(&self) -> (Option<ArrayString<64>>, Option<ArrayString<64>>, u64) {
+        (self.title_compare, self.artist_compare, self.album_id)
+    }
+}
+
+impl PartialEq for LibraryAlbumEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.album_id == other.album_id
+    }
+}
+
+impl Eq for LibraryAlbumEntry {}
+
+impl PartialOrd for LibraryAlbumEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Albums with the same title are told apart by artist, so each album has its own place
+impl Ord for LibraryAlbumEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.compare_values().cmp(&other.compare_values())
+    }
+}
+
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+pub struct LibraryGenreEntry {
+    pub genre_id: u64,
+    pub name_compare: Option<ArrayString<32>>,
+    pub name_display: Option<ArrayString<32>>,
+}
+
+impl LibraryGenreEntry {
+    pub fn new(name: Option<ArrayString<32>>) -> LibraryGenreEntry {
+        LibraryGenreEntry{
+            genre_id: name.map(|name| LibraryGenreEntry::id_for_name(&name)).unwrap_or(NO_GENRE_ID),
+            name_display: name,
+            name_compare: name.map(|name| str_trunc(&name.to_lowercase())),
+        }
+    }
+
+    /// Genres are the same regardless of case
+    pub fn id_for_name(name: &str) -> u64 {
+        hash(&name.to_lowercase())
+    }
+}
+
+impl PartialEq for LibraryGenreEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.genre_id == other.genre_id
+    }
+}
+
+impl Eq for LibraryGenreEntry {}
+
+impl PartialOrd for LibraryGenreEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LibraryGenreEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.name_compare.cmp(&other.name_compare)
+    }
+}
+
 #[derive(Clone, Copy)]
 #[derive(Debug)]
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
 pub enum LibraryFilterEntry {
     All,
     Artist(LibraryArtistEntry),
+    Album(LibraryAlbumEntry),
     Year{year: Option<u16>},
+    Genre(LibraryGenreEntry),
 }
 
 impl LibraryFilterEntry {
-    pub fn name(&self) -> ArrayString<64> {
+    pub fn name(&self) -> ArrayString<160> {
         match self {
             LibraryFilterEntry::All => ArrayString::from_str("ALL"),
             LibraryFilterEntry::Artist(artist) => match artist.name_display {
-                Some(name) => Ok(name),
+                Some(name) => ArrayString::from_str(&name),
                 None       => ArrayString::from_str("<missing>"),
             },
+            LibraryFilterEntry::Album(album) => {
+                let mut name = ArrayString::new();
+                name.push_str(album.title_display.as_deref().unwrap_or("<missing>"));
+                if let Some(artist) = album.artist_display {
+                    name.push_str(" — ");
+                    name.push_str(&artist);
+                }
+                if let Some(year) = album.year {
+                    name.push_str(" (");
+                    name.push_str(&year.to_string());
+                    name.push_str(")");
+                }
+                Ok(name)
+            },
             LibraryFilterEntry::Year { year } => match year {
                 Some(year) => ArrayString::from_str(&year.to_string()),
                 None       => ArrayString::from_str("----"),
             },
+            LibraryFilterEntry::Genre(genre) => match genre.name_display {
+                Some(name) => ArrayString::from_str(&name),
+                None       => ArrayString::from_str("<missing>"),
+            },
         }.unwrap()
     }
 }
@@ -206,3 +485,76 @@ impl Listable for LibraryFilterEntry {
 //-////////////////////////////////////////////////////////////////////////////
 //
 //-////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_gain_multiplier() {
+        assert_eq!(parse_replay_gain("-6.02 dB"), Some(-6.02));
+        assert_eq!(parse_replay_gain(" +3.5dB "), Some(3.5));
+        assert_eq!(parse_replay_gain("0.988525"), Some(0.988525));
+        assert_eq!(parse_replay_gain("loud"), None);
+
+        let gain = ReplayGain{
+            track_gain: Some(-6.0),
+            track_peak: Some(0.5),
+            album_gain: Some(6.0),
+            album_peak: Some(0.8),
+        };
+        assert!((gain.multiplier(false, 0.0) - 0.501).abs() < 0.001);
+        // +6 dB would clip the album peak of 0.8
+        assert!((gain.multiplier(true, 0.0) - 1.25).abs() < 0.001);
+
+        let untagged = ReplayGain::default();
+        assert!((untagged.multiplier(true, -6.0) - 0.501).abs() < 0.001);
+        assert_eq!(untagged.multiplier(false, 0.0), 1.0);
+    }
+
+    #[test]
+    fn test_genres_from_tag() {
+        let names = |genres: Genres| genres.iter().flatten().map(|genre| genre.to_string()).collect::<Vec<String>>();
+        assert_eq!(names(TrackFile::genres_from_tag(" Rock \0Jazz\0rock\0\0")), vec!["Rock", "Jazz"]);
+        assert_eq!(names(TrackFile::genres_from_tag("a\0b\0c\0d\0e")), vec!["a", "b", "c", "d"]);
+        assert_eq!(names(TrackFile::genres_from_tag("")), Vec::<String>::new());
+
+        let genres = TrackFile::genres_from_tag("Rock\0Jazz");
+        let track  = TrackFile{genres, genre_ids: TrackFile::ids_for_genres(&genres), ..TrackFile::test(1)};
+        assert!(track.has_genre(LibraryGenreEntry::id_for_name("rock")));
+        assert!(!track.has_genre(NO_GENRE_ID));
+        assert_eq!(TrackFile::test(2).genre_filter_ids().collect::<Vec<u64>>(), vec![NO_GENRE_ID]);
+        assert_eq!(TrackFile::test(2).genre_entries()[0].genre_id, NO_GENRE_ID);
+    }
+
+    #[test]
+    fn test_str_trunc_multibyte() {
+        let genre = "ポストロックとエレクトロニカの音楽";
+        assert_eq!(str_trunc::<32>(genre).as_str(), "ポストロックとエレク>");
+        assert_eq!(str_trunc::<32>("short").as_str(), "short");
+
+        let genres = TrackFile::genres_from_tag(genre);
+        assert_eq!(genres[0].map(|genre| genre.to_string()), Some("ポストロックとエレク>".to_string()));
+        assert_eq!(LibraryGenreEntry::new(genres[0]).name_compare, genres[0]);
+    }
+
+    #[test]
+    fn test_album_entries() {
+        let album = |artist: &str, title: &str, year: Option<u16>| {
+            LibraryFilterEntry::Album(LibraryAlbumEntry::from_track(TrackFile{year, ..TrackFile::test(0)}.with_album(artist, title)))
+        };
+        let mut albums = [
+            album("Various Artists", "The Hits", Some(1999)),
+            album("Band", "Greatest Hits", None),
+            album("Another Band", "Greatest Hits", Some(2001)),
+            album("Band", "Abbey Road", Some(1969)),
+        ];
+        albums.sort();
+        assert_eq!(albums.iter().map(|album| album.name().to_string()).collect::<Vec<String>>(), vec![
+            "Abbey Road — Band (1969)",
+            "Greatest Hits — Another Band (2001)",
+            "Greatest Hits — Band",
+            "The Hits — Various Artists (1999)",
+        ]);
+    }
+}
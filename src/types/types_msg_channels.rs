@@ -1,4 +1,5 @@
 use crate::tasks::listener_playback::PlaybackActions;
+use crate::tasks::listener_scanner::ScanActions;
 use crate::tasks::listener_state::StateActions;
 use crate::tasks::listener_tui::RenderActions;
 use crate::tasks::listener_updater::UpdateActions;
@@ -18,6 +19,7 @@ pub struct MsgChannels {
     pub update  : Sender<UpdateActions>,
     pub delay   : Sender<Instant>,
     pub tui     : Sender<RenderActions>,
+    pub scan    : Sender<ScanActions>,
 }
 //-////////////////////////////////////////////////////////////////////////////
 //
@@ -161,6 +161,11 @@ impl TermState {
             self.text_buf.push_str(prefix);
         }
         self.text_buf.push_str(string);
+        self.fit_text_buf(len);
+    }
+
+    /// Like `fit_str` for text that has already been written to `text_buf`
+    pub fn fit_text_buf(&mut self, len: usize) {
         fit_text_to_term(&mut self.text_buf, len);
         self.frame.push_str(&self.text_buf);
     }
@@ -91,6 +91,14 @@ impl<'a, T: Clone + Copy + Debug + Eq + Ord + Listable> SortedListState<T> {
         }
     }
 
+    /// Selects the first entry matching `predicate`, returns false if there is none
+    pub fn select_where(&mut self, predicate: impl Fn(&T) -> bool) -> bool {
+        match self.entries.iter().position(predicate) {
+            Some(index) => {self.selected = index; true},
+            None        => false,
+        }
+    }
+
     // -- insert ----------------------------------------------------
 
     pub fn add(&mut self, element: T) {
@@ -112,6 +120,29 @@ impl<'a, T: Clone + Copy + Debug + Eq + Ord + Listable> SortedListState<T> {
         }
     }
 
+    /// Removes every entry matching `predicate`, the selection stays on its entry or moves to the next selectable one
+    pub fn remove_where(&mut self, predicate: impl Fn(&T) -> bool) {
+        let mut index = 0;
+        let mut removed_above_selected = 0;
+        let mut removed_above_anchor   = 0;
+        let mut is_selected_removed    = false;
+        self.entries.retain(|entry| {
+            let remove = predicate(entry);
+            if remove {
+                removed_above_selected += (index < self.selected) as usize;
+                removed_above_anchor   += (index < self.scroll_anchor) as usize;
+                is_selected_removed    |= index == self.selected;
+            }
+            index += 1;
+            !remove
+        });
+        self.selected      -= removed_above_selected;
+        self.scroll_anchor -= removed_above_anchor;
+        if is_selected_removed || !self.selected_entry().is_some_and(|entry| entry.is_selectable()) {
+            self.select_next(0);
+        }
+    }
+
     pub fn replace_all(&mut self, elements: Vec<T>) {
         self.entries = elements;
         self.select_start();
@@ -308,6 +339,29 @@ mod tests {
             ],
         });
     }
+
+    #[test]
+    fn test_sorted_list_remove_element() {
+        let mut state = SortedListState{
+            unique       : false,
+            selected     : 3,
+            scroll_anchor: 1,
+            entries      : vec![
+                DummyElement::new(false),
+                DummyElement::new(true ),
+                DummyElement::new(false),
+                DummyElement::new(true ),
+            ],
+        };
+
+        // selection follows its entry
+        state.remove_where(|entry| !entry.selectable);
+        assert_eq!((state.selected, state.scroll_anchor, state.len()), (1, 0, 2));
+
+        // nothing left to select
+        state.remove_where(|_| true);
+        assert_eq!((state.selected, state.scroll_anchor, state.len()), (0, 0, 0));
+    }
 }
 
 //-////////////////////////////////////////////////////////////////////////////
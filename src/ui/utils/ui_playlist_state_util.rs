@@ -0,0 +1,31 @@
+use crate::state::state_playlist::PlaylistState;
+use crate::tasks::listener_tui::RenderDataCommon;
+use crate::types::types_style::Color;
+use crate::types::types_style::Theme;
+use crate::types::types_tui::TermState;
+
+/// Icon for where a row is in the playlist, broken tracks are marked unless they are playing
+pub fn render_playlist_state(
+    output: &mut TermState,
+    common: &RenderDataCommon,
+    theme: Theme,
+    playlist_state: PlaylistState,
+    is_broken: bool,
+) {
+    let theme = match (playlist_state, is_broken) {
+        (PlaylistState::Playing, _    ) => theme.recolor(common.theme.icon_color_playing),
+        (_                     , true ) => theme.recolor(common.theme.icon_color_broken),
+        (PlaylistState::None   , false) => theme.recolor(Color::Default),
+        (PlaylistState::Played , false) => theme.recolor(common.theme.icon_color_done),
+        (PlaylistState::Queued , false) => theme.recolor(common.theme.icon_color_queued),
+    };
+    let icon = match (playlist_state, is_broken) {
+        (PlaylistState::Playing, _    ) => common.playback.state.icon(),
+        (_                     , true ) => '✗',
+        (PlaylistState::None   , false) => ' ',
+        (PlaylistState::Played , false) => '-',
+        (PlaylistState::Queued , false) => '+',
+    };
+    output.style_theme(theme);
+    output.frame.push(icon);
+}
@@ -1,14 +1,15 @@
 use crate::state::state_library::LibraryColumn;
 use crate::state::state_library::LibrarySelectMode;
 use crate::state::state_library::LibraryTab;
-use crate::state::state_playlist::PlaylistState;
 use crate::tasks::listener_tui::RenderDataCommon;
 use crate::types::types_library_entry::LibraryFilterEntry;
 use crate::types::types_library_entry::TrackFile;
 use crate::types::types_tui::TermState;
 use crate::types::types_style::Color;
 use crate::types::types_style::Theme;
+use crate::ui::utils::ui_playlist_state_util::render_playlist_state;
 use crate::ui::utils::ui_time_util::render_duration;
+use std::collections::BTreeSet;
 use std::fmt::Write;
 use std::iter::repeat;
 use std::str::FromStr;
@@ -28,6 +29,7 @@ pub struct RenderDataViewLibrary {
     pub left_selected: usize,
     pub right: Vec<TrackFile>,
     pub right_selected: usize,
+    pub broken: BTreeSet<u64>,
 }
 
 pub fn draw_library_view(
@@ -83,6 +85,7 @@ pub fn draw_library_view(
                 *track,
                 view.column_selected == LibraryColumn::Tracks,
                 i == view.right_selected,
+                view.broken.contains(&track.id_track),
             ),
             None => {
                 output.style_empty();
@@ -121,10 +124,26 @@ fn render_header(
         output.fit_str(Some("filter by "), tab_name, filter_width.saturating_sub(2));
     }
 
-    // select mode
+    // select mode, and scan progress while scanning
     {
         let select_name: &'static str = select_mode.into();
-        output.fit_str(Some("   select "), select_name, width.saturating_sub(filter_width));
+        output.text_buf.clear();
+        output.text_buf.push_str("   select ");
+        output.text_buf.push_str(select_name);
+        if common.term.is_scanning {
+            let scan = common.term.scan;
+            output.text_buf.push_str("   found ");
+            output.text_buf.push_str(output.num_buf.format(scan.found));
+            output.text_buf.push_str("   parsed ");
+            output.text_buf.push_str(output.num_buf.format(scan.parsed));
+            output.text_buf.push_str("   failed ");
+            output.text_buf.push_str(output.num_buf.format(scan.failed));
+            if let Some(eta) = scan.eta {
+                output.text_buf.push_str("   eta ");
+                render_duration(&mut output.text_buf, eta);
+            }
+        }
+        output.fit_text_buf(width.saturating_sub(filter_width));
     }
 }
 //-////////////////////////////////////////////////////////////////////////////
@@ -139,7 +158,6 @@ fn render_filter_row(
     is_selected: bool,
 ) {
     let playlist_state = common.playlist.get_playback_state_for_filter(entry);
-    let playback_state = common.playback.state;
 
     let theme = Theme {
         color_base: common.theme.selectable_normal,
@@ -152,22 +170,7 @@ fn render_filter_row(
     };
 
     // playback indicator
-    {
-        let theme = match playlist_state {
-            PlaylistState::None    => theme.recolor(Color::Default),
-            PlaylistState::Played  => theme.recolor(common.theme.icon_color_done),
-            PlaylistState::Playing => theme.recolor(common.theme.icon_color_playing),
-            PlaylistState::Queued  => theme.recolor(common.theme.icon_color_queued),
-        };
-        let icon = match playlist_state {
-            PlaylistState::None    => ' ',
-            PlaylistState::Played  => '-',
-            PlaylistState::Playing => playback_state.icon(),
-            PlaylistState::Queued  => '+',
-        };
-        output.style_theme(theme);
-        output.frame.push(icon);
-    }
+    render_playlist_state(output, common, theme, playlist_state, false);
 
     // filter name
     {
@@ -242,9 +245,9 @@ fn render_track_row(
     track: TrackFile,
     is_active: bool,
     is_selected: bool,
+    is_broken: bool,
 ) {
     let playlist_state = common.playlist.get_playback_state_for_track(track.id_track);
-    let playback_state = common.playback.state;
 
     let len_padding  = 5;
     let len_playback = 1;
@@ -263,22 +266,7 @@ fn render_track_row(
     };
 
     // playback indicator
-    {
-        let theme = match playlist_state {
-            PlaylistState::None    => theme.recolor(Color::Default),
-            PlaylistState::Played  => theme.recolor(common.theme.icon_color_done),
-            PlaylistState::Playing => theme.recolor(common.theme.icon_color_playing),
-            PlaylistState::Queued  => theme.recolor(common.theme.icon_color_queued),
-        };
-        let icon = match playlist_state {
-            PlaylistState::None    => ' ',
-            PlaylistState::Played  => '-',
-            PlaylistState::Playing => playback_state.icon(),
-            PlaylistState::Queued  => '+',
-        };
-        output.style_theme(theme);
-        output.frame.push(icon);
-    }
+    render_playlist_state(output, common, theme, playlist_state, is_broken);
 
     output.frame.push(' ');
 
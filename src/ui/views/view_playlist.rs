@@ -0,0 +1,162 @@
+use crate::state::state_playlist::PlaylistEntry;
+use crate::state::state_playlist::PlaylistState;
+use crate::tasks::listener_tui::RenderDataCommon;
+use crate::types::types_library_entry::TrackFile;
+use crate::types::types_tui::TermState;
+use crate::types::types_style::Color;
+use crate::types::types_style::Theme;
+use crate::ui::utils::ui_playlist_state_util::render_playlist_state;
+use crate::ui::utils::ui_time_util::render_duration;
+use std::collections::BTreeSet;
+use std::iter::repeat_n;
+use std::time::Duration;
+use unicode_width::UnicodeWidthStr;
+
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+#[derive(Debug)]
+pub struct RenderDataViewPlaylist {
+    pub list_height: usize,
+    pub rows: Vec<(PlaylistEntry, PlaylistState)>,
+    pub cursor: usize,
+    pub track_count: usize,
+    pub total: Duration,
+    pub remaining: Duration,
+    pub broken: BTreeSet<u64>,
+}
+
+pub fn draw_playlist_view(
+    output: &mut TermState,
+    common: &RenderDataCommon,
+    view: RenderDataViewPlaylist,
+) {
+    let width = common.term.width as usize;
+
+    render_header(output, common, width, &view);
+
+    for i in 0..view.list_height {
+        output.newline();
+
+        match view.rows.get(i).copied() {
+            Some((entry, state)) => render_track_row(
+                output,
+                common,
+                width,
+                state,
+                entry.track,
+                i == view.cursor,
+                view.broken.contains(&entry.track.id_track),
+            ),
+            None => {
+                output.style_empty();
+                output.frame.extend(repeat_n(' ', width));
+            },
+        }
+    }
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+fn render_header(
+    output: &mut TermState,
+    common: &RenderDataCommon,
+    width: usize,
+    view: &RenderDataViewPlaylist,
+) {
+    output.style(Color::Black, common.theme.border, true);
+
+    // loading icon
+    {
+        let loading_icon = match common.term.is_scanning {
+            true  => common.term.loading_icon(),
+            false => ' ',
+        };
+        output.frame.push(loading_icon);
+    }
+
+    output.frame.push(' ');
+
+    // track count and durations
+    {
+        output.text_buf.clear();
+        output.text_buf.push_str("playlist   ");
+        output.text_buf.push_str(output.num_buf.format(view.track_count));
+        output.text_buf.push_str(" tracks   ");
+        render_duration(&mut output.text_buf, view.total);
+        output.text_buf.push_str(" total   ");
+        render_duration(&mut output.text_buf, view.remaining);
+        output.text_buf.push_str(" left");
+        output.fit_text_buf(width.saturating_sub(2));
+    }
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
+fn render_track_row(
+    output: &mut TermState,
+    common: &RenderDataCommon,
+    width: usize,
+    playlist_state: PlaylistState,
+    track: TrackFile,
+    is_selected: bool,
+    is_broken: bool,
+) {
+
+    let len_padding  = 4;
+    let len_playback = 1;
+    let len_duration = 5 + 3 * (track.duration.as_secs() > 3600) as usize;
+    let len_dynamic  = width.saturating_sub(len_padding + len_playback + len_duration);
+
+    let theme = Theme {
+        color_base: match playlist_state {
+            PlaylistState::Played => common.theme.icon_color_done,
+            _ => common.theme.selectable_normal,
+        },
+        color_selected: common.theme.selectable_highlight_active,
+        is_selected,
+        bold: false,
+    };
+
+    // playback indicator
+    render_playlist_state(output, common, theme, playlist_state, is_broken);
+
+    output.frame.push(' ');
+
+    // track name
+    let len_artist = {
+        let track_name = track.track_title;
+        let len_track = match len_dynamic.saturating_sub(track_name.width()) {
+            0   => len_dynamic,
+            1.. => track_name.width(),
+        };
+        output.style_theme(theme);
+        output.fit_str(None, &track_name, len_track);
+        len_dynamic.saturating_sub(track_name.width())
+    };
+
+    // artist name
+    match (len_artist, track.track_artist.or(track.album_artist)) {
+        (0  , _   ) => {},
+        (_  , None) => output.frame.extend(repeat_n(' ', len_artist)),
+        (1.., Some(artist)) => {
+            output.style_theme(theme.recolor(common.theme.track_artist_name));
+            output.fit_str(Some(" - "), &artist, len_artist);
+        },
+    }
+
+    output.frame.push(' ');
+
+    // track duration
+    {
+        output.text_buf.clear();
+        render_duration(&mut output.text_buf, track.duration);
+        output.style_theme(theme.recolor(common.theme.track_highlight));
+        output.frame.push_str(&output.text_buf);
+    }
+
+    output.frame.push_str("  ");
+}
+//-////////////////////////////////////////////////////////////////////////////
+//
+//-////////////////////////////////////////////////////////////////////////////
@@ -1,10 +1,13 @@
 use crate::globals::playback_state::PlaybackState;
 use crate::globals::terminal_state::GlobalUiState;
+use crate::state::state_playlist::PlaylistRepeat;
+use crate::state::state_playlist::PlaylistStates;
 use crate::tasks::listener_tui::RenderDataCommon;
 use crate::types::types_style::Color;
 use crate::types::types_tui::TermState;
 use crate::ui::utils::ui_time_util::render_duration;
 use std::iter::repeat;
+use std::iter::repeat_n;
 
 //-////////////////////////////////////////////////////////////////////////////
 //
@@ -16,7 +19,7 @@ pub fn render_playback_status_widget(
     let width = common.term.width as usize;
     output.style(Color::Black, common.theme.border, false);
 
-    if width < 20 {
+    if width < 30 {
         output.frame.extend(repeat(' ').take(width));
         return;
     }
@@ -27,15 +30,30 @@ pub fn render_playback_status_widget(
     output.frame.push(playback.state.icon());
     output.frame.extend(repeat(' ').take(1));
 
+    if let Some(command) = common.command {
+        output.fit_str(Some(":"), &command, width-6);
+        output.frame.push_str("  ");
+        return;
+    }
+
+    if let Some(status) = common.status {
+        output.fit_str(None, &status, width-6);
+        output.frame.push_str("  ");
+        return;
+    }
+
     match playback.state {
         PlaybackState::Stopped |
         PlaybackState::Loading => {
-            let progress_width = width-20;
+            let progress_width = width-30;
             GlobalUiState::update_progress_width(progress_width);
             output.frame.push_str("--:--/--:-- [");
             output.frame.extend(repeat('/').take(progress_width));
-            output.frame.push_str("]  ");
-            //  --:--/--:-- [///////////]
+            output.frame.push(']');
+            render_modes(output, &common.playlist);
+            render_volume(output, playback.volume, playback.muted);
+            output.frame.push_str("  ");
+            //  --:--/--:-- [///////////] ⤮↻1∞ 100%
         },
         PlaybackState::Paused |
         PlaybackState::Playing => {
@@ -55,8 +73,10 @@ pub fn render_playback_status_widget(
             let status_width     = 2;
             let whitespace_width = 5;
             let progress_width   = 3;
+            let modes_width      = 5;
+            let volume_width     = 5;
             let duration_width   = output.text_buf.len();
-            let remaining_width  = width.saturating_sub(status_width + whitespace_width + progress_width + duration_width);
+            let remaining_width  = width.saturating_sub(status_width + whitespace_width + progress_width + modes_width + volume_width + duration_width);
             GlobalUiState::update_progress_width(remaining_width);
 
             let pre_progress  = ((remaining_width as f64 * progress) as usize).min(remaining_width);
@@ -67,8 +87,41 @@ pub fn render_playback_status_widget(
             output.frame.push('➤');
             output.frame.extend(repeat('⋅').take(post_progress));
             output.frame.push(']');
+            render_modes(output, &common.playlist);
+            render_volume(output, playback.volume, playback.muted);
             output.frame.extend(repeat(' ').take(2));
-            //  00:30/01:00 [━━━━━➤⋅⋅⋅⋅⋅]
+            //  00:30/01:00 [━━━━━➤⋅⋅⋅⋅⋅] ⤮↻1∞ 100%
+        },
+    }
+}
+
+/// Shuffle, repeat and radio indicators, always 5 characters wide
+fn render_modes(output: &mut TermState, playlist: &PlaylistStates) {
+    output.frame.push(' ');
+    output.frame.push(match playlist.shuffle {
+        true  => '⤮',
+        false => ' ',
+    });
+    output.frame.push_str(match playlist.repeat {
+        PlaylistRepeat::Off => "  ",
+        PlaylistRepeat::All => "↻ ",
+        PlaylistRepeat::One => "↻1",
+    });
+    output.frame.push(match playlist.radio {
+        true  => '∞',
+        false => ' ',
+    });
+}
+
+/// Always 5 characters wide
+fn render_volume(output: &mut TermState, volume: u8, muted: bool) {
+    match muted {
+        true  => output.frame.push_str(" mute"),
+        false => {
+            let number = output.num_buf.format(volume.min(100));
+            output.frame.extend(repeat_n(' ', 4 - number.len()));
+            output.frame.push_str(number);
+            output.frame.push('%');
         },
     }
 }